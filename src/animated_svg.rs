@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use svg::Document;
+
+/// Markup of one object, split into attribute values and everything around them.
+struct Fragment {
+    template: Vec<String>,
+    values: Vec<String>,
+}
+
+impl Fragment {
+    fn new(markup: &str) -> Self {
+        let mut template = vec![String::new()];
+        let mut values: Vec<String> = Vec::new();
+        let mut in_tag = false;
+        let mut in_value = false;
+        for c in markup.chars() {
+            if in_value {
+                if c == '"' {
+                    in_value = false;
+                    template.push(c.to_string());
+                } else {
+                    values.last_mut().unwrap().push(c);
+                }
+                continue;
+            }
+            template.last_mut().unwrap().push(c);
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                '"' if in_tag => {
+                    in_value = true;
+                    values.push(String::new());
+                }
+                _ => {}
+            }
+        }
+        Self { template, values }
+    }
+}
+
+/// Same object in consecutive frames, from `first` to `last`.
+struct Track {
+    first: usize,
+    last: usize,
+    template: Vec<String>,
+    // values from the frame where they changed
+    states: Vec<(usize, Vec<String>)>,
+}
+
+impl Track {
+    fn can_continue(&self, fragment: &Fragment) -> bool {
+        let values = &self.states.last().unwrap().1;
+        if self.template != fragment.template {
+            return false;
+        }
+        // only attributes of a single element can be animated, and `;` separates animated values
+        let elements = self
+            .template
+            .iter()
+            .map(|part| part.matches('<').count() - part.matches("</").count())
+            .sum::<usize>();
+        values
+            .iter()
+            .zip(fragment.values.iter())
+            .all(|(a, b)| a == b || (elements == 1 && !a.contains(';') && !b.contains(';')))
+    }
+
+    fn markup(&self, values: &[String]) -> String {
+        let mut markup = self.template[0].clone();
+        for (value, part) in values.iter().zip(self.template[1..].iter()) {
+            markup.push_str(value);
+            markup.push_str(part);
+        }
+        markup
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Id(i32),
+    Markup(String),
+}
+
+/// Single svg with all frames, switched by SMIL animations. Objects that stay the same between
+/// frames are written once, objects with the same `id` get their attributes animated.
+pub struct AnimatedSvg {
+    frame_duration: f64,
+    total_frames: usize,
+    tracks: Vec<Track>,
+    // tracks in the order they have to be drawn
+    order: Vec<usize>,
+    active: HashMap<(Key, usize), usize>,
+}
+
+impl AnimatedSvg {
+    pub fn new(frame_duration: f64) -> Self {
+        Self {
+            frame_duration,
+            total_frames: 0,
+            tracks: Vec::new(),
+            order: Vec::new(),
+            active: HashMap::new(),
+        }
+    }
+
+    /// Objects of the next frame as markup, along with their ids.
+    pub fn push_frame(&mut self, objects: impl IntoIterator<Item = (Option<i32>, String)>) {
+        let frame = self.total_frames;
+        self.total_frames += 1;
+
+        let mut occurrences = HashMap::new();
+        let mut active = HashMap::new();
+        for (id, markup) in objects {
+            if markup.is_empty() {
+                continue;
+            }
+            let fragment = Fragment::new(&markup);
+            let key = match id {
+                Some(id) => Key::Id(id),
+                None => Key::Markup(markup),
+            };
+            // repeated objects in one frame are told apart by their order
+            let occurrence = occurrences.entry(key.clone()).or_insert(0);
+            let key = (key, *occurrence);
+            *occurrence += 1;
+
+            let track = match self.active.remove(&key) {
+                Some(ind) if self.tracks[ind].can_continue(&fragment) => {
+                    let track = &mut self.tracks[ind];
+                    if track.states.last().unwrap().1 != fragment.values {
+                        track.states.push((frame, fragment.values));
+                    }
+                    track.last = frame;
+                    ind
+                }
+                _ => {
+                    self.tracks.push(Track {
+                        first: frame,
+                        last: frame,
+                        template: fragment.template,
+                        states: vec![(frame, fragment.values)],
+                    });
+                    self.order.push(self.tracks.len() - 1);
+                    self.tracks.len() - 1
+                }
+            };
+            active.insert(key, track);
+        }
+        self.active = active;
+    }
+
+    fn animate(&self, attribute: &str, values: &[String], key_times: &[usize]) -> String {
+        let key_times = key_times
+            .iter()
+            .map(|&frame| (frame as f64 / self.total_frames as f64).to_string())
+            .collect::<Vec<_>>();
+        format!(
+            "<animate attributeName=\"{}\" values=\"{}\" keyTimes=\"{}\" dur=\"{}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>",
+            attribute,
+            values.join(";"),
+            key_times.join(";"),
+            self.total_frames as f64 * self.frame_duration
+        )
+    }
+
+    fn visibility(&self, first: usize, last: usize) -> Option<String> {
+        let mut values = Vec::new();
+        let mut key_times = Vec::new();
+        if first > 0 {
+            values.push("hidden".to_string());
+            key_times.push(0);
+        }
+        values.push("visible".to_string());
+        key_times.push(first);
+        if last + 1 < self.total_frames {
+            values.push("hidden".to_string());
+            key_times.push(last + 1);
+        }
+        (values.len() > 1).then(|| self.animate("visibility", &values, &key_times))
+    }
+
+    fn track_markup(&self, track: &Track) -> String {
+        let markup = track.markup(&track.states[0].1);
+        if track.states.len() == 1 {
+            return markup;
+        }
+
+        let mut animations = String::new();
+        for (ind, part) in track.template[..track.template.len() - 1].iter().enumerate() {
+            let mut values = Vec::new();
+            let mut key_times = Vec::new();
+            for (frame, state) in track.states.iter() {
+                if values.last() != Some(&state[ind]) {
+                    values.push(state[ind].clone());
+                    key_times.push(if key_times.is_empty() { 0 } else { *frame });
+                }
+            }
+            if values.len() > 1 {
+                let attribute = part.trim_end_matches("=\"").rsplit(char::is_whitespace).next().unwrap();
+                animations.push_str(&self.animate(attribute, &values, &key_times));
+            }
+        }
+
+        // animations go inside the element, after the last attribute
+        let tail = track.template.last().unwrap();
+        let end = markup.len() - tail.len() + tail.find('>').unwrap();
+        if markup[..end].ends_with('/') {
+            let name = track.template[0][1..].split(char::is_whitespace).next().unwrap();
+            format!("{}>{}</{}>{}", &markup[..end - 1], animations, name, &markup[end + 1..])
+        } else {
+            format!("{}>{}{}", &markup[..end], animations, &markup[end + 1..])
+        }
+    }
+
+    /// Writes all frames on top of `canvas`, which is shown all the time.
+    pub fn save(&self, canvas: Document, file_name: &str) -> io::Result<()> {
+        let mut content = String::new();
+        let mut group: Option<(usize, String)> = None;
+        for &ind in self.order.iter() {
+            let track = &self.tracks[ind];
+            let markup = self.track_markup(track);
+            // objects shown for a single frame share the group of the frame
+            if track.first == track.last && self.total_frames > 1 {
+                match &mut group {
+                    Some((frame, objects)) if *frame == track.first => {
+                        objects.push('\n');
+                        objects.push_str(&markup);
+                    }
+                    _ => {
+                        if let Some((frame, objects)) = group.replace((track.first, markup)) {
+                            content.push_str(&self.group(frame, frame, &objects));
+                        }
+                    }
+                }
+                continue;
+            }
+            if let Some((frame, objects)) = group.take() {
+                content.push_str(&self.group(frame, frame, &objects));
+            }
+            content.push_str(&self.group(track.first, track.last, &markup));
+        }
+        if let Some((frame, objects)) = group.take() {
+            content.push_str(&self.group(frame, frame, &objects));
+        }
+
+        let canvas = canvas.to_string();
+        let svg = match canvas.strip_suffix("/>") {
+            Some(start) => format!("{}>{}\n</svg>", start, content),
+            None => {
+                let end = canvas.rfind("</svg>").unwrap_or(canvas.len());
+                format!("{}{}\n{}", &canvas[..end], content, &canvas[end..])
+            }
+        };
+        fs::write(file_name, svg)
+    }
+
+    // viewers without animations show the first frame
+    fn group(&self, first: usize, last: usize, objects: &str) -> String {
+        match self.visibility(first, last) {
+            Some(animation) => {
+                let visibility = if first == 0 { "visible" } else { "hidden" };
+                format!("\n<g visibility=\"{}\">{}\n{}\n</g>", visibility, animation, objects)
+            }
+            None => format!("\n{}", objects),
+        }
+    }
+}
+
+/// Content of a document made for a single object.
+pub fn object_markup(document: &Document) -> String {
+    let document = document.to_string();
+    match (document.find('>'), document.rfind("</svg>")) {
+        (Some(start), Some(end)) if start < end => document[start + 1..end].trim().to_string(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,142 @@
+use crate::raster;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use gif::{Encoder, Repeat};
+use png::{BitDepth, ColorType};
+use resvg::tiny_skia::Pixmap;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Gif,
+    Apng,
+}
+
+pub struct AnimationParams {
+    pub width: u32,
+    // in seconds
+    pub frame_duration: f64,
+    // from 1 (best colors) to 30 (fastest), only used for gif
+    pub palette_speed: i32,
+}
+
+/// Frame rendered for an animation.
+pub struct Image {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Image {
+    fn new(pixmap: Pixmap) -> Self {
+        let rgba = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        Self {
+            width: pixmap.width(),
+            height: pixmap.height(),
+            rgba,
+        }
+    }
+}
+
+pub fn render(svg: &str, width: u32) -> Result<Image, String> {
+    raster::render(svg, width).map(Image::new)
+}
+
+/// Encodes frames as one animation, they are written as soon as they come.
+/// Fewer than `total_frames` images is an error.
+pub fn save(
+    format: Format,
+    file_name: &str,
+    images: impl Iterator<Item = Result<Image, String>>,
+    total_frames: usize,
+    params: &AnimationParams,
+) -> Result<(), String> {
+    let writer = BufWriter::new(File::create(file_name).map_err(|e| e.to_string())?);
+    let mut received = 0;
+    let images = images.inspect(|_| received += 1);
+    match format {
+        Format::Gif => save_gif(writer, images, total_frames, params)?,
+        Format::Apng => save_apng(writer, images, total_frames, params)?,
+    }
+    if received < total_frames {
+        return Err(format!("Only {} of {} frames were rendered", received, total_frames));
+    }
+    Ok(())
+}
+
+// delays are rounded to the time from the start, so that errors don't accumulate
+fn delays(total_frames: usize, frame_duration: f64, units_per_second: f64) -> impl Iterator<Item = u64> {
+    let time = move |ind: usize| (ind as f64 * frame_duration * units_per_second).round() as u64;
+    (0..total_frames).map(move |ind| time(ind + 1) - time(ind))
+}
+
+fn first<I: Iterator<Item = (Result<Image, String>, u64)>>(frames: &mut I) -> Result<(Image, u64), String> {
+    match frames.next() {
+        Some((image, delay)) => Ok((image?, delay)),
+        None => Err("No frames to save".to_string()),
+    }
+}
+
+fn save_gif(
+    writer: impl Write,
+    images: impl Iterator<Item = Result<Image, String>>,
+    total_frames: usize,
+    params: &AnimationParams,
+) -> Result<(), String> {
+    let mut frames = images.zip(delays(total_frames, params.frame_duration, 100.0));
+    let (image, delay) = first(&mut frames)?;
+    if image.width > u16::MAX as u32 || image.height > u16::MAX as u32 {
+        return Err(format!("Image {}x{} is too large for gif", image.width, image.height));
+    }
+    let mut encoder = Encoder::new(writer, image.width as u16, image.height as u16, &[]).map_err(|e| e.to_string())?;
+    encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+    let mut write = |(mut image, delay): (Image, u64)| {
+        let mut frame = gif::Frame::from_rgba_speed(image.width as u16, image.height as u16, &mut image.rgba, params.palette_speed);
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        encoder.write_frame(&frame).map_err(|e| e.to_string())
+    };
+
+    // identical consecutive frames are written once with a longer delay
+    let mut pending = (image, delay);
+    for (image, delay) in frames {
+        let image = image?;
+        if image.rgba == pending.0.rgba {
+            pending.1 += delay;
+        } else {
+            write(std::mem::replace(&mut pending, (image, delay)))?;
+        }
+    }
+    write(pending)
+}
+
+fn save_apng(
+    writer: impl Write,
+    images: impl Iterator<Item = Result<Image, String>>,
+    total_frames: usize,
+    params: &AnimationParams,
+) -> Result<(), String> {
+    let mut frames = images.zip(delays(total_frames, params.frame_duration, 1000.0));
+    let (image, delay) = first(&mut frames)?;
+    let mut encoder = png::Encoder::new(writer, image.width, image.height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_animated(total_frames as u32, 0).map_err(|e| e.to_string())?;
+    let mut encoder = encoder.write_header().map_err(|e| e.to_string())?;
+
+    for (image, delay) in std::iter::once((Ok(image), delay)).chain(frames) {
+        let image = image?;
+        encoder
+            .set_frame_delay(delay.min(u16::MAX as u64) as u16, 1000)
+            .map_err(|e| e.to_string())?;
+        encoder.write_image_data(&image.rgba).map_err(|e| e.to_string())?;
+    }
+    encoder.finish().map_err(|e| e.to_string())
+}
@@ -1,28 +1,147 @@
-use crate::figure::Figure;
+use crate::export_config::ExportConfig;
+use crate::job::JobStatus;
+use crate::loader::LoadUpdate;
+use crate::merge::Layer;
+use crate::replay::{Frames, Header};
 
-use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::sync::Arc;
 
-use druid::{Data, Lens, Size};
+use druid::{Data, Lens, Point};
 
 #[derive(Clone, Data)]
 pub struct DrawProperties {
     pub width: f64,
     pub font: f64,
     pub was_messages: usize,
+    // set if the replay is merged with others
+    #[data(ignore)]
+    pub layer: Option<Layer>,
+}
+
+impl DrawProperties {
+    pub fn new() -> Self {
+        Self {
+            width: 1.0,
+            font: 1.0,
+            was_messages: 0,
+            layer: None,
+        }
+    }
+}
+
+/// One of the views compared side by side, with its own set of shown tags.
+#[derive(Clone)]
+pub struct Pane {
+    pub tags: Arc<Vec<(String, bool)>>,
+    // only tags of this group are shown at first, e.g. `A/` for the first of overlaid replays
+    pub group: Option<String>,
+}
+
+impl Pane {
+    pub fn new(group: Option<String>) -> Self {
+        Self {
+            tags: Arc::new(Vec::new()),
+            group,
+        }
+    }
 }
 
 #[derive(Clone, Data, Lens)]
 pub struct AppData {
-    pub objects: Arc<Mutex<Vec<Box<dyn Figure + Send>>>>,
-    pub frames: Arc<Mutex<Vec<Vec<usize>>>>,
+    pub frames: Arc<Frames>,
     pub frame: usize,
-    pub fps_speed: Arc<Mutex<f64>>,
-    pub size: Arc<Mutex<Size>>,
-    pub tags: Arc<Mutex<Vec<(String, bool)>>>,
-    pub draw_properties: Arc<Mutex<DrawProperties>>,
-    pub svg_width_scale: Arc<Mutex<f64>>,
-    pub flipy: Arc<Mutex<bool>>,
-    pub shift: Arc<Mutex<Size>>,
-
-    pub finished: Arc<Mutex<bool>>,
+    pub header: Arc<Header>,
+    pub tags: Arc<Vec<(String, bool)>>,
+    pub export: ExportConfig,
+    // the last export, running or finished
+    pub job: Option<JobStatus>,
+    // empty if there is only one view
+    pub panes: Arc<Vec<Pane>>,
+    // number of overlaid replays, see `merge::Layer`
+    pub layers: usize,
+    // center and scale shared by all panes while their cameras are linked
+    pub linked: bool,
+    pub camera: Option<(Point, f64)>,
+    // how many times the replay was read again after it changed
+    pub reloads: usize,
+    // the replay keeps growing, playback waits at its end
+    pub following: bool,
+
+    pub finished: bool,
+}
+
+impl AppData {
+    pub fn new(export: ExportConfig) -> Self {
+        Self {
+            frames: Arc::new(Frames::default()),
+            frame: 0,
+            header: Arc::new(Header::default()),
+            tags: Arc::new(Vec::new()),
+            export,
+            job: None,
+            panes: Arc::new(Vec::new()),
+            layers: 0,
+            linked: true,
+            camera: None,
+            reloads: 0,
+            following: false,
+            finished: false,
+        }
+    }
+
+    pub fn is_exporting(&self) -> bool {
+        self.job.as_ref().is_some_and(|job| !job.finished)
+    }
+
+    pub fn apply(&mut self, update: LoadUpdate) {
+        self.apply_update(update, &HashSet::new());
+    }
+
+    /// Replaces the replay with the one read again, the current frame and the shown tags stay.
+    pub fn reload(&mut self, update: LoadUpdate) {
+        let kept = self.tags.iter().map(|(tag, _)| tag.clone()).collect::<HashSet<_>>();
+        self.frames = Arc::new(Frames::default());
+        self.apply_update(update, &kept);
+        self.frame = self.frame.min(self.frames.len().saturating_sub(1));
+        self.reloads += 1;
+    }
+
+    // `disable` settings don't change tags in `kept`, they were already shown or hidden by the user
+    fn apply_update(&mut self, update: LoadUpdate, kept: &HashSet<String>) {
+        if let Some(index) = update.index {
+            self.frames = Arc::new(Frames::Indexed(index));
+        } else if !update.frames.is_empty() || !update.provisional.is_empty() || self.frames.has_provisional() {
+            if let Frames::Loaded(chunks) = Arc::make_mut(&mut self.frames) {
+                chunks.push_chunk(update.frames.into());
+                chunks.set_provisional(update.provisional);
+            }
+        }
+        if *self.header != update.header {
+            self.header = Arc::new(update.header);
+        }
+        if !update.tags.is_empty() || !update.disabled.is_empty() {
+            for pane in Arc::make_mut(&mut self.panes).iter_mut() {
+                add_tags(Arc::make_mut(&mut pane.tags), &update.tags, &update.disabled, pane.group.as_deref(), kept);
+            }
+            add_tags(Arc::make_mut(&mut self.tags), &update.tags, &update.disabled, None, kept);
+        }
+        self.finished = update.finished;
+    }
+}
+
+// tags of other groups than `group` are added disabled
+fn add_tags(tags: &mut Vec<(String, bool)>, new_tags: &[(String, bool)], disabled: &[String], group: Option<&str>, kept: &HashSet<String>) {
+    let in_group = |tag: &str| group.is_none_or(|group| tag.starts_with(group));
+    let new_tags = new_tags.iter().filter(|(tag, _)| !kept.contains(tag));
+    tags.extend(new_tags.map(|(tag, enabled)| (tag.clone(), *enabled && in_group(tag))));
+    for (tag, enabled) in tags.iter_mut() {
+        if disabled.contains(tag) && !kept.contains(tag) {
+            *enabled = false;
+        }
+    }
+}
+
+pub fn enabled_tags(tags: &[(String, bool)]) -> HashSet<String> {
+    tags.iter().filter(|(_, b)| *b).map(|(tag, _)| tag.clone()).collect()
 }
@@ -1,10 +1,10 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use druid::kurbo::{BezPath, Size};
 use druid::piet::{LineCap, LineJoin, RenderContext, StrokeStyle};
 use druid::theme;
 use druid::widget::prelude::*;
-use druid::{Color, Point, Rect};
+use druid::{Point, Rect};
 
 use druid::{Command, Selector, Target};
 
@@ -41,8 +41,8 @@ impl Checklist {
     }
 }
 
-impl Widget<Arc<Mutex<Vec<(String, bool)>>>> for Checklist {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut Arc<Mutex<Vec<(String, bool)>>>, env: &Env) {
+impl Widget<Arc<Vec<(String, bool)>>> for Checklist {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut Arc<Vec<(String, bool)>>, env: &Env) {
         match event {
             Event::MouseDown(_) => {
                 ctx.set_active(true);
@@ -52,12 +52,19 @@ impl Widget<Arc<Mutex<Vec<(String, bool)>>>> for Checklist {
                 if ctx.is_active() {
                     ctx.set_active(false);
                     if ctx.is_hot() {
-                        self.selected = self.get_selected_index(e.pos.y, env.get(theme::BASIC_WIDGET_HEIGHT), data.lock().unwrap().len());
+                        self.selected = self.get_selected_index(e.pos.y, env.get(theme::BASIC_WIDGET_HEIGHT), data.len());
                         if let Some(ind) = self.selected {
-                            if data.lock().unwrap()[ind].1 {
-                                data.lock().unwrap()[ind].1 = false;
-                            } else {
-                                data.lock().unwrap()[ind].1 = true;
+                            let tags = Arc::make_mut(data);
+                            let enabled = !tags[ind].1;
+                            // a group like `A/` switches all of its tags
+                            let group = Some(tags[ind].0.clone()).filter(|tag| tag.ends_with('/'));
+                            match group {
+                                Some(group) => {
+                                    for (_, val) in tags.iter_mut().filter(|(key, _)| key.starts_with(&group)) {
+                                        *val = enabled;
+                                    }
+                                }
+                                None => tags[ind].1 = enabled,
                             }
                         }
                         if let Some(id) = self.notify_widget {
@@ -68,7 +75,7 @@ impl Widget<Arc<Mutex<Vec<(String, bool)>>>> for Checklist {
                 }
             }
             Event::MouseMove(e) => {
-                let new_selected = self.get_selected_index(e.pos.y, env.get(theme::BASIC_WIDGET_HEIGHT), data.lock().unwrap().len());
+                let new_selected = self.get_selected_index(e.pos.y, env.get(theme::BASIC_WIDGET_HEIGHT), data.len());
                 if new_selected != self.selected {
                     self.selected = new_selected;
                     ctx.request_paint();
@@ -78,30 +85,27 @@ impl Widget<Arc<Mutex<Vec<(String, bool)>>>> for Checklist {
         }
     }
 
-    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &Arc<Mutex<Vec<(String, bool)>>>, _env: &Env) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &Arc<Vec<(String, bool)>>, _env: &Env) {
         if let LifeCycle::HotChanged(_) = event {
             ctx.request_paint();
         }
     }
 
-    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &Arc<Mutex<Vec<(String, bool)>>>, _data: &Arc<Mutex<Vec<(String, bool)>>>, _env: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &Arc<Vec<(String, bool)>>, _data: &Arc<Vec<(String, bool)>>, _env: &Env) {
         ctx.request_paint();
     }
 
-    fn layout(&mut self, _ctx: &mut LayoutCtx, _bc: &BoxConstraints, data: &Arc<Mutex<Vec<(String, bool)>>>, env: &Env) -> Size {
-        Size::new(
-            self.width,
-            env.get(theme::BASIC_WIDGET_HEIGHT) * data.lock().unwrap().len() as f64 * HEIGHT_KOEF,
-        )
+    fn layout(&mut self, _ctx: &mut LayoutCtx, _bc: &BoxConstraints, data: &Arc<Vec<(String, bool)>>, env: &Env) -> Size {
+        Size::new(self.width, env.get(theme::BASIC_WIDGET_HEIGHT) * data.len() as f64 * HEIGHT_KOEF)
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &Arc<Mutex<Vec<(String, bool)>>>, env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Arc<Vec<(String, bool)>>, env: &Env) {
         let size = env.get(theme::BASIC_WIDGET_HEIGHT);
         let border_width = 1.;
 
         let mut position = size * (HEIGHT_KOEF - 1.0) / 2.0;
         let mut ind: usize = 0;
-        for (key, val) in data.lock().unwrap().iter() {
+        for (key, val) in data.iter() {
             let rect = Rect::from_origin_size(Point::new(0.0, position), Size::new(size, size))
                 .inset(-border_width / 2.)
                 .to_rounded_rect(2.);
@@ -130,7 +134,7 @@ impl Widget<Arc<Mutex<Vec<(String, bool)>>>> for Checklist {
             let layout = text
                 .new_text_layout(key.clone())
                 .font(FontFamily::SANS_SERIF, size * 0.7)
-                .text_color(Color::rgb8(0xff, 0xff, 0xff))
+                .text_color(env.get(theme::TEXT_COLOR))
                 .alignment(TextAlignment::Start)
                 .build()
                 .unwrap();
@@ -0,0 +1,317 @@
+use crate::export_config::{parse_ranges, ExportConfig};
+use crate::merge::Layer;
+use crate::parse::Param;
+use crate::settings::{self, get_settings};
+use crate::slice::Selection;
+
+use druid::Color;
+
+use std::path::Path;
+
+pub const USAGE: &str = "\
+Usage:
+  rviewer [view] [FILE...] [OPTIONS]       open FILE, or the standard input, in a window,
+                                           several files are overlaid tick by tick
+  rviewer export [FILE...] --format FORMAT [OPTIONS]
+                                           export without a window and exit
+  rviewer validate [FILE]                  check the replay and report problems
+  rviewer stats [FILE...] [--json] [--lazy]
+                                           print numbers of ticks, frames, objects and tags
+  rviewer slice [FILE] [--frames LIST] [--ticks LIST] [--tags LIST] [--figures LIST] [--output FILE]
+                                           write a smaller replay with only the selected ticks
+                                           and objects, to the standard output by default
+  rviewer merge FILE FILE... [-o FILE]     write the files overlaid as one replay
+  rviewer diff FILE FILE [--json] [--open] [--lazy]
+                                           compare two replays frame by frame and report
+                                           the first difference
+  rviewer config                           print the settings and the files they are read from
+
+Formats: png, svg, gif, apng, animated-svg, html, sheet-png, sheet-svg, mp4
+
+Options:
+  --out DIR            folder for exported files
+  --template NAME      names of frame files, e.g. {name}_{frame:05}
+  --frames LIST        frames to export, e.g. 10..50 or 1,50,100..120
+  --ticks LIST         ticks to export, the same way
+  --every N            export only every nth frame
+  --overwrite MODE     rename, skip or replace existing files
+  --width PIXELS       width of png frames and animations (export only)
+  --tags LIST          only show these tags, e.g. a,b (export and slice)
+  --figures LIST       only objects of these figures, e.g. rect,text (slice only)
+  --lazy               don't load the whole file into memory
+  --json               print stats or differences as json (stats and diff)
+  --open               show both files side by side at the first difference (diff only)
+  --compare            show files side by side instead of overlaid, or one file twice (view only)
+  --follow             keep reading the file as it grows, like tail -f (view only)
+  --tint NAME=COLOR    mix colors of an overlaid file with a color, e.g. B=(255,0,0)
+  --opacity NAME=X     opacity of an overlaid file, e.g. B=0.5
+  --set KEY=VALUE      override a setting, e.g. theme=light or max_threads=8 (all commands)
+
+Overlaid files are named A, B, C and so on, their tags are shown under A/, B/, ...
+";
+
+// options without a value
+const FLAGS: [&str; 5] = ["lazy", "json", "compare", "open", "follow"];
+
+const FIGURES: [&str; 7] = ["rect", "circle", "line", "grid", "poly", "text", "msg"];
+
+// what `rviewer export` can make, in the same order as in `USAGE`
+pub const FORMATS: [&str; 9] = ["png", "svg", "gif", "apng", "animated-svg", "html", "sheet-png", "sheet-svg", "mp4"];
+
+pub enum Command {
+    View(Input),
+    Export(Input, ExportArgs),
+    // the standard input if there is no path
+    Validate(Option<String>),
+    // with json output if set
+    Stats(Input, bool),
+    Slice(Option<String>, SliceArgs),
+    Merge(Vec<String>, Vec<Layer>, Option<String>),
+    // with json output and then the window at the first difference if set
+    Diff(Input, bool, bool),
+    Config,
+    Help,
+}
+
+/// Replay to read, the standard input if there are no paths. Several replays are overlaid,
+/// with a layer for each of them.
+pub struct Input {
+    pub paths: Vec<String>,
+    pub layers: Vec<Layer>,
+    pub lazy: bool,
+    // side by side panes in the window
+    pub compare: bool,
+    // the file is still being written
+    pub follow: bool,
+    pub export: ExportConfig,
+}
+
+pub struct ExportArgs {
+    pub format: String,
+    pub width: Option<u32>,
+    // only these tags are shown, all of them if not set
+    pub tags: Option<Vec<String>>,
+}
+
+pub struct SliceArgs {
+    // the standard output if not set
+    pub output: Option<String>,
+    pub selection: Selection,
+}
+
+/// Options are `--key value` or `--key=value`, without a subcommand the file is opened in a window.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let (subcommand, args) = match args.first().map(String::as_str) {
+        Some(subcommand @ ("view" | "export" | "validate" | "stats" | "slice" | "merge" | "diff" | "config")) => (subcommand, &args[1..]),
+        Some("help") => return Ok(Command::Help),
+        _ => ("view", args),
+    };
+    let export = subcommand == "export";
+
+    let mut paths = Vec::new();
+    let mut flags = Vec::new();
+    let mut options = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some("help") => return Ok(Command::Help),
+            Some(flag) if FLAGS.contains(&flag) => flags.push(flag),
+            Some(option) => match option.split_once('=') {
+                Some((key, value)) => options.push((key.to_string(), value.to_string())),
+                None => {
+                    let value = args.next().ok_or(format!("no value for --{}", option))?;
+                    options.push((option.to_string(), value.clone()));
+                }
+            },
+            None if arg == "-h" => return Ok(Command::Help),
+            None if arg == "-o" => {
+                let value = args.next().ok_or("no value for -o")?;
+                options.push(("output".to_string(), value.clone()));
+            }
+            None => paths.push(arg.clone()),
+        }
+    }
+    if let (Some(arg), "validate" | "slice") = (paths.get(1), subcommand) {
+        return Err(format!("unexpected argument \"{}\"", arg));
+    }
+    if subcommand == "merge" && paths.len() < 2 {
+        return Err("merge needs at least two files".to_string());
+    }
+    if subcommand == "diff" && paths.len() != 2 {
+        return Err("diff needs two files".to_string());
+    }
+    if paths.len() > 26 {
+        return Err("at most 26 files can be overlaid".to_string());
+    }
+    let mut layers = match paths.len() {
+        1 => Vec::new(),
+        count => (0..count).map(|ind| Layer::new(ind, count)).collect(),
+    };
+    let mut layer_options = Vec::new();
+    let mut overrides = Vec::new();
+    for (key, value) in std::mem::take(&mut options) {
+        match key.as_str() {
+            "set" => overrides.push(value),
+            "tint" | "opacity" if layers.is_empty() => return Err(format!("--{} is only for several files", key)),
+            "tint" | "opacity" => layer_options.push((key, value)),
+            _ => options.push((key, value)),
+        }
+    }
+    for (key, value) in layer_options {
+        set_layer_option(&mut layers, &key, &value).map_err(|e| format!("--{}: {}", key, e))?;
+    }
+    settings::set_overrides(&overrides).map_err(|e| format!("--set: {}", e))?;
+
+    let json = flags.contains(&"json");
+    if json && !matches!(subcommand, "stats" | "diff") {
+        return Err("--json is only for stats and diff".to_string());
+    }
+    let compare = flags.contains(&"compare");
+    if compare && subcommand != "view" {
+        return Err("--compare is only for view".to_string());
+    }
+    let follow = flags.contains(&"follow");
+    if follow && subcommand != "view" {
+        return Err("--follow is only for view".to_string());
+    }
+    if follow && paths.len() != 1 {
+        return Err("--follow needs one file, the standard input is followed anyway".to_string());
+    }
+    let open = flags.contains(&"open");
+    if open && subcommand != "diff" {
+        return Err("--open is only for diff".to_string());
+    }
+    if let (Some((key, _)), "validate" | "stats" | "diff") = (options.first(), subcommand) {
+        return Err(format!("{} has no option --{}", subcommand, key));
+    }
+    if subcommand == "config" {
+        return match (paths.first(), options.first()) {
+            (Some(arg), _) => Err(format!("unexpected argument \"{}\"", arg)),
+            (_, Some((key, _))) => Err(format!("config has no option --{}", key)),
+            _ => Ok(Command::Config),
+        };
+    }
+    if subcommand == "validate" {
+        return Ok(Command::Validate(paths.pop()));
+    }
+    if subcommand == "slice" {
+        return parse_slice(paths.pop(), options);
+    }
+    if subcommand == "merge" {
+        let mut output = None;
+        for (key, value) in options {
+            match key.as_str() {
+                "output" => output = Some(value),
+                _ => return Err(format!("merge has no option --{}", key)),
+            }
+        }
+        return Ok(Command::Merge(paths, layers, output));
+    }
+
+    // the first file names exported files
+    let name = paths
+        .first()
+        .and_then(|path| Path::new(path).file_stem())
+        .map_or("stdin".to_string(), |name| name.to_string_lossy().to_string());
+    let mut input = Input {
+        paths,
+        layers,
+        lazy: flags.contains(&"lazy"),
+        compare,
+        follow,
+        export: ExportConfig::new(&get_settings(), &name),
+    };
+    let mut export_args = ExportArgs {
+        format: String::new(),
+        width: None,
+        tags: None,
+    };
+    for (key, value) in options {
+        match key.as_str() {
+            "format" | "width" | "tags" if !export => return Err(format!("{} has no option --{}", subcommand, key)),
+            "format" if FORMATS.contains(&value.as_str()) => export_args.format = value,
+            "format" => return Err(format!("unknown format \"{}\", expected one of {}", value, FORMATS.join(", "))),
+            "width" => match value.parse() {
+                Ok(width) if width > 0 => export_args.width = Some(width),
+                _ => return Err(format!("bad width \"{}\"", value)),
+            },
+            "tags" => export_args.tags = Some(list(&value)),
+            _ => input.export.set_option(&key, &value).map_err(|e| format!("--{}: {}", key, e))?,
+        }
+    }
+
+    if subcommand == "stats" {
+        Ok(Command::Stats(input, json))
+    } else if subcommand == "diff" {
+        Ok(Command::Diff(input, json, open))
+    } else if !export {
+        Ok(Command::View(input))
+    } else if export_args.format.is_empty() {
+        match get_settings().export_format {
+            Some(format) if FORMATS.contains(&format.as_str()) => {
+                export_args.format = format;
+                Ok(Command::Export(input, export_args))
+            }
+            Some(format) => Err(format!(
+                "unknown export_format \"{}\" in settings, expected one of {}",
+                format,
+                FORMATS.join(", ")
+            )),
+            None => Err(format!("--format is required, one of {}", FORMATS.join(", "))),
+        }
+    } else {
+        Ok(Command::Export(input, export_args))
+    }
+}
+
+// `NAME=VALUE`, e.g. `B=(255,0,0)` for a tint
+fn set_layer_option(layers: &mut [Layer], key: &str, value: &str) -> Result<(), String> {
+    let (name, value) = value.split_once('=').ok_or("expected NAME=VALUE")?;
+    let layer = layers
+        .iter_mut()
+        .find(|layer| layer.name == name)
+        .ok_or(format!("no file named {}", name))?;
+    match key {
+        "tint" => layer.tint = Some(<Color as Param>::from(&[value]).ok_or(format!("bad color \"{}\"", value))?),
+        _ => match value.parse() {
+            Ok(opacity) if (0.0..=1.0).contains(&opacity) => layer.opacity = opacity,
+            _ => return Err(format!("bad opacity \"{}\", expected 0..1", value)),
+        },
+    }
+    Ok(())
+}
+
+fn parse_slice(path: Option<String>, options: Vec<(String, String)>) -> Result<Command, String> {
+    let mut args = SliceArgs {
+        output: None,
+        selection: Selection::default(),
+    };
+    for (key, value) in options {
+        let selection = &mut args.selection;
+        match key.as_str() {
+            "output" => args.output = Some(value),
+            "frames" => selection.frames = parse_ranges(&value).map_err(|e| format!("--frames: {}", e))?,
+            "ticks" => selection.ticks = parse_ranges(&value).map_err(|e| format!("--ticks: {}", e))?,
+            "tags" => selection.tags = Some(list(&value)),
+            "figures" => {
+                let figures = list(&value);
+                if let Some(name) = figures.iter().find(|name| !FIGURES.contains(&name.as_str())) {
+                    return Err(format!("unknown figure \"{}\", expected some of {}", name, FIGURES.join(", ")));
+                }
+                selection.figures = Some(figures);
+            }
+            _ => return Err(format!("slice has no option --{}", key)),
+        }
+    }
+    Ok(Command::Slice(path, args))
+}
+
+// comma separated, empty items are skipped
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
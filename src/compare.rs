@@ -0,0 +1,56 @@
+use crate::app_data::{enabled_tags, Pane};
+use crate::merge;
+use crate::replay::{Frame, FrameRef};
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Ids of objects that pane number `pane` shows and some other pane doesn't. Objects of
+/// overlaid replays are matched by their ids in their own replays.
+pub fn unmatched_ids(frame: &Frame, panes: &[Pane], pane: usize, layers: usize) -> HashSet<i32> {
+    let shown = panes
+        .iter()
+        .map(|pane| {
+            let tags = enabled_tags(&pane.tags);
+            frame
+                .objects
+                .iter()
+                .filter(|item| item.need_to_draw(&tags))
+                .filter_map(|item| item.common().id)
+                .map(|id| merge::source_id(id, layers))
+                .collect::<HashSet<_>>()
+        })
+        .collect::<Vec<_>>();
+    shown[pane]
+        .iter()
+        .filter(|id| shown.iter().any(|other| !other.contains(id)))
+        .copied()
+        .collect()
+}
+
+/// Keeps `unmatched_ids` of the last painted frame, they are found again only when the frame
+/// or the tags of the panes change.
+#[derive(Default)]
+pub struct UnmatchedCache {
+    frame: Option<FrameRef>,
+    tags: Vec<Arc<Vec<(String, bool)>>>,
+    ids: Arc<HashSet<i32>>,
+}
+
+impl UnmatchedCache {
+    pub fn get(&mut self, frame: &FrameRef, panes: &[Pane], pane: usize, layers: usize) -> Arc<HashSet<i32>> {
+        let same_frame = self.frame.as_ref().is_some_and(|cached| std::ptr::eq::<Frame>(&**cached, &**frame));
+        let same_tags = self.tags.len() == panes.len()
+            && self
+                .tags
+                .iter()
+                .zip(panes)
+                .all(|(tags, pane)| Arc::ptr_eq(tags, &pane.tags) || *tags == pane.tags);
+        if !same_frame || !same_tags {
+            self.frame = Some(frame.clone());
+            self.tags = panes.iter().map(|pane| pane.tags.clone()).collect();
+            self.ids = Arc::new(unmatched_ids(frame, panes, pane, layers));
+        }
+        self.ids.clone()
+    }
+}
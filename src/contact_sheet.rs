@@ -0,0 +1,119 @@
+use crate::overlay;
+
+use druid::{Color, Point, Rect, Size};
+
+use svg::node::element::Rectangle as SvgRect;
+use svg::node::element::Text as SvgText;
+use svg::node::Text as SvgText2;
+use svg::Document;
+
+// caption height relative to the width of a thumbnail
+const CAPTION_SCALE: f64 = 1.0 / 14.0;
+
+/// Placement of thumbnails in rows, with a caption under each of them. Units are pixels of the sheet.
+#[derive(Clone, Copy)]
+pub struct Grid {
+    columns: usize,
+    cell: Size,
+    font: f64,
+}
+
+impl Grid {
+    /// `region` is the part of frames shown in thumbnails.
+    pub fn new(columns: usize, cell_width: f64, region: Rect) -> Self {
+        Self {
+            columns: columns.max(1),
+            cell: Size::new(cell_width, cell_width * region.height() / region.width()),
+            font: cell_width * CAPTION_SCALE,
+        }
+    }
+
+    /// Where the thumbnail number `ind` goes.
+    pub fn cell(&self, ind: usize) -> Rect {
+        let gap = self.font;
+        let x = gap + (ind % self.columns) as f64 * (self.cell.width + gap);
+        let y = gap + (ind / self.columns) as f64 * (self.cell.height + self.font * 1.5 + gap);
+        Rect::from_origin_size(Point::new(x, y), self.cell)
+    }
+
+    fn size(&self, count: usize) -> Size {
+        let columns = count.clamp(1, self.columns);
+        let rows = count.div_ceil(self.columns);
+        let gap = self.font;
+        Size::new(
+            gap + columns as f64 * (self.cell.width + gap),
+            gap + rows as f64 * (self.cell.height + self.font * 1.5 + gap),
+        )
+    }
+}
+
+/// Thumbnails of several frames in one image.
+pub struct ContactSheet {
+    grid: Grid,
+    text_color: Color,
+    count: usize,
+    content: String,
+}
+
+impl ContactSheet {
+    pub fn new(grid: Grid, text_color: Color) -> Self {
+        Self {
+            grid,
+            text_color,
+            count: 0,
+            content: String::new(),
+        }
+    }
+
+    /// Adds the next thumbnail, `frame` is an image already placed at `grid.cell` of its number.
+    pub fn push(&mut self, frame: &str, caption: &str) {
+        let cell = self.grid.cell(self.count);
+        let color = overlay::color_to_string(&self.text_color);
+        let border = SvgRect::new()
+            .set("x", cell.x0)
+            .set("y", cell.y0)
+            .set("width", cell.width())
+            .set("height", cell.height())
+            .set("fill", "none")
+            .set("stroke", color.clone())
+            .set("stroke-opacity", 0.3);
+        let caption = SvgText::new()
+            .add(SvgText2::new(caption))
+            .set("x", cell.center().x)
+            .set("y", cell.y1 + self.grid.font * 1.2)
+            .set("fill", color)
+            .set("font-size", self.grid.font)
+            .set("text-anchor", "middle")
+            .set("font-family", "sans-serif");
+        self.content += &format!("\n{}\n{}\n{}", frame, border, caption);
+        self.count += 1;
+    }
+
+    /// Markup of the whole sheet and its width in pixels.
+    pub fn finish(self, background: Option<Color>) -> (String, u32) {
+        let size = self.grid.size(self.count);
+        let mut img = Document::new()
+            .set("viewBox", (0, 0, size.width, size.height))
+            .set("width", size.width)
+            .set("height", size.height);
+        if let Some(color) = background {
+            let rect = SvgRect::new()
+                .set("width", size.width)
+                .set("height", size.height)
+                .set("fill", overlay::color_to_string(&color))
+                .set("opacity", color.as_rgba().3);
+            img = img.add(rect);
+        }
+
+        // thumbnails are already serialized, so they go right before the end of the sheet
+        let img = img.to_string();
+        let svg = match img.strip_suffix("/>") {
+            Some(start) => format!("{}>{}\n</svg>", start, self.content),
+            None => {
+                let end = img.rfind("</svg>").unwrap_or(img.len());
+                format!("{}{}\n{}", &img[..end], self.content, &img[end..])
+            }
+        };
+        (svg, size.width.round() as u32)
+    }
+}
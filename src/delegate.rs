@@ -0,0 +1,28 @@
+use crate::app_data::AppData;
+use crate::job::JOB_UPDATE;
+use crate::loader::LoadUpdate;
+
+use druid::{AppDelegate, Command, DelegateCtx, Env, Handled, Selector, SingleUse, Target};
+
+pub const LOAD_UPDATE: Selector<SingleUse<LoadUpdate>> = Selector::new("load_update");
+// the whole replay, read again after the file changed
+pub const RELOAD: Selector<SingleUse<LoadUpdate>> = Selector::new("reload");
+
+pub struct Delegate;
+
+impl AppDelegate<AppData> for Delegate {
+    fn command(&mut self, _ctx: &mut DelegateCtx, _target: Target, cmd: &Command, data: &mut AppData, _env: &Env) -> Handled {
+        if let Some(update) = cmd.get(LOAD_UPDATE).and_then(SingleUse::take) {
+            data.apply(update);
+            Handled::Yes
+        } else if let Some(update) = cmd.get(RELOAD).and_then(SingleUse::take) {
+            data.reload(update);
+            Handled::Yes
+        } else if let Some(status) = cmd.get(JOB_UPDATE) {
+            data.job = Some(status.clone());
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::figure::{figure_name, Object};
+use crate::replay::{Frame, Frames};
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Differences of two replays, frames and ticks are numbered from 1.
+#[derive(Serialize)]
+pub struct Diff {
+    pub frames: [usize; 2],
+    pub different_frames: usize,
+    // the first frame that differs, with what differs in it
+    pub first: Option<FrameDiff>,
+}
+
+#[derive(Serialize)]
+pub struct FrameDiff {
+    pub frame: usize,
+    pub tick: usize,
+    // objects only in the first replay, as lines of the replay file
+    pub removed: Vec<String>,
+    // objects only in the second one
+    pub added: Vec<String>,
+    pub changed: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+    pub object: String,
+    // values in the first and the second replay
+    pub properties: BTreeMap<String, [Value; 2]>,
+}
+
+// objects with an id are matched by it, the rest by figure and geometry
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Id(i32),
+    Shape(String),
+}
+
+fn key(item: &Object) -> Key {
+    match item.common().id {
+        Some(id) => Key::Id(id),
+        None => {
+            let mut properties = properties(item);
+            properties.retain(|name, _| !["col", "t", "k", "fu"].contains(&name.as_str()));
+            Key::Shape(serde_json::to_string(&properties).unwrap())
+        }
+    }
+}
+
+fn describe(item: &Object) -> String {
+    match item.common().id {
+        Some(id) => format!("{} id={}", figure_name(item), id),
+        None => item.to_line(),
+    }
+}
+
+// named as in the replay file, json of figures has no keep, id and function
+fn properties(item: &Object) -> BTreeMap<String, Value> {
+    let mut properties = match item.to_json() {
+        Value::Object(map) => map.into_iter().collect(),
+        _ => BTreeMap::new(),
+    };
+    let common = item.common();
+    if common.keep {
+        properties.insert("k".to_string(), Value::from(1));
+    }
+    if let Some(id) = common.id {
+        properties.insert("id".to_string(), Value::from(id));
+    }
+    if let Some(func) = &common.func {
+        properties.insert("fu".to_string(), Value::from(func.as_str()));
+    }
+    properties
+}
+
+fn objects(frame: Option<&Frame>) -> &[Object] {
+    frame.map_or(&[], |frame| &frame.objects)
+}
+
+/// `None` if the frames are the same. A missing frame differs from any other one.
+fn diff_frame(ind: usize, a: Option<&Frame>, b: Option<&Frame>) -> Option<FrameDiff> {
+    let (a_objects, b_objects) = (objects(a), objects(b));
+    let same = a_objects.len() == b_objects.len() && a_objects.iter().zip(b_objects).all(|(x, y)| properties(x) == properties(y));
+    if a.is_some() && b.is_some() && same {
+        return None;
+    }
+
+    // equal objects without an id are matched in the order they are drawn
+    let mut unmatched = HashMap::<Key, Vec<&Object>>::new();
+    for item in a_objects.iter().rev() {
+        unmatched.entry(key(item)).or_default().push(item);
+    }
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for item in b_objects.iter() {
+        let old = match unmatched.get_mut(&key(item)).and_then(Vec::pop) {
+            Some(old) => old,
+            None => {
+                added.push(item.to_line());
+                continue;
+            }
+        };
+        let (old_properties, new_properties) = (properties(old), properties(item));
+        let mut names = old_properties.keys().chain(new_properties.keys()).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let properties = names
+            .into_iter()
+            .map(|name| {
+                let value = |properties: &BTreeMap<String, Value>| properties.get(name).cloned().unwrap_or(Value::Null);
+                (name.clone(), [value(&old_properties), value(&new_properties)])
+            })
+            .filter(|(_, [old, new])| old != new)
+            .collect::<BTreeMap<_, _>>();
+        if !properties.is_empty() {
+            changed.push(Change {
+                object: describe(old),
+                properties,
+            });
+        }
+    }
+    // in the order of the first replay
+    let removed = a_objects
+        .iter()
+        .filter(|item| unmatched.get(&key(item)).is_some_and(|left| left.iter().any(|x| std::ptr::eq(*x, *item))))
+        .map(|item| item.to_line())
+        .collect::<Vec<_>>();
+
+    let tick = a.or(b).map_or(0, |frame| frame.tick);
+    Some(FrameDiff {
+        frame: ind + 1,
+        tick: tick + 1,
+        removed,
+        added,
+        changed,
+    })
+    .filter(|diff| a.is_none() || b.is_none() || !diff.removed.is_empty() || !diff.added.is_empty() || !diff.changed.is_empty())
+}
+
+/// Goes through all frames of both replays, for indexed files they are parsed here.
+pub fn compare(a: &Frames, b: &Frames) -> Diff {
+    let mut diff = Diff {
+        frames: [a.len(), b.len()],
+        different_frames: 0,
+        first: None,
+    };
+    for ind in 0..a.len().max(b.len()) {
+        let (a_frame, b_frame) = (a.get(ind), b.get(ind));
+        if let Some(frame_diff) = diff_frame(ind, a_frame.as_deref(), b_frame.as_deref()) {
+            diff.different_frames += 1;
+            if diff.first.is_none() {
+                diff.first = Some(frame_diff);
+            }
+        }
+    }
+    diff
+}
+
+impl Diff {
+    pub fn print(&self, names: [&str; 2]) {
+        println!("{}: {} frames, {}: {} frames", names[0], self.frames[0], names[1], self.frames[1]);
+        let first = match &self.first {
+            Some(first) => first,
+            None => {
+                println!("no differences");
+                return;
+            }
+        };
+        println!(
+            "{} frames differ, the first is frame {} (tick {}):",
+            self.different_frames, first.frame, first.tick
+        );
+        for line in first.removed.iter() {
+            println!("  - {}", line);
+        }
+        for line in first.added.iter() {
+            println!("  + {}", line);
+        }
+        for change in first.changed.iter() {
+            let properties = change
+                .properties
+                .iter()
+                .map(|(name, [old, new])| format!("{} {} -> {}", name, old, new))
+                .collect::<Vec<_>>();
+            println!("  ~ {}: {}", change.object, properties.join(", "));
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use crate::animated_svg::{self, AnimatedSvg};
+use crate::animation::{self, AnimationParams, Format};
+use crate::app_data::AppData;
+use crate::contact_sheet::{self, ContactSheet};
+use crate::export_config::NoPath;
+use crate::export_options::ExportOptions;
+use crate::external::{self, Converter};
+use crate::figure::{MMessage, Object};
+use crate::html_export;
+use crate::job::{Job, JobStatus};
+use crate::overlay;
+use crate::parallel;
+use crate::raster;
+use crate::replay::Header;
+use crate::settings::get_settings;
+use crate::svg_params::SvgParams;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use druid::ExtEventSink;
+
+use svg::node::element::Rectangle as SvgRect;
+use svg::Document;
+
+use threadpool::ThreadPool;
+
+/// Export running in the background: the first status of its job, to be shown right away,
+/// and the thread that returns the final one.
+/// `None` if there is nothing to export, the reason is already printed.
+pub type Export = Option<(JobStatus, JoinHandle<JobStatus>)>;
+
+pub fn save_frame_as_svg(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    if data.frame >= data.frames.len() {
+        return None;
+    }
+
+    let frame = data.frame;
+    let path = match output_path(data, &frame_file_name(data, frame, "svg"), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+    let job = Job::new(format!("Saving frame {} as {}", frame + 1, path.display()), 1, sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            report_frame(&job, frame, save_svg(&data, frame, &options, &path));
+            job.finish()
+        }),
+    ))
+}
+
+pub fn save_all_frames_as_svg(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    save_frames(data, "svg", sink, move |data, frame, path, _| save_svg(data, frame, &options, path))
+}
+
+pub fn save_frame_as_png(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    if data.frame >= data.frames.len() {
+        return None;
+    }
+
+    let frame = data.frame;
+    let path = match output_path(data, &frame_file_name(data, frame, "png"), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+    let settings = get_settings();
+    let width = options.width(&data.header, settings.frame_resolution.unwrap());
+    let svg = make_svg(data, frame, &options).to_string();
+    let converter = Converter::new(&settings);
+    let job = Job::new(format!("Saving frame {} as {}", frame + 1, path.display()), 1, sink);
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            report_frame(&job, frame, save_png(&svg, width, &path, converter.as_ref(), &job));
+            job.finish()
+        }),
+    ))
+}
+
+pub fn save_all_frames_as_png(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    let settings = get_settings();
+    let width = options.width(&data.header, settings.frame_resolution.unwrap());
+    let converter = Converter::new(&settings);
+    save_frames(data, "png", sink, move |data, frame, path, job| {
+        let svg = make_svg(data, frame, &options).to_string();
+        save_png(&svg, width, path, converter.as_ref(), job)
+    })
+}
+
+pub fn export_animation(data: &AppData, options: ExportOptions, format: Format, sink: Option<ExtEventSink>) -> Export {
+    let frames = export_frames(data)?;
+    let extension = match format {
+        Format::Gif => "gif",
+        Format::Apng => "png",
+    };
+    let path = match output_path(data, &format!("{}.{}", data.export.name, extension), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+
+    let settings = get_settings();
+    let threads = settings.max_threads.unwrap();
+    let params = AnimationParams {
+        width: options.width(&data.header, settings.animation_resolution.unwrap()),
+        frame_duration: data.header.fps_speed,
+        palette_speed: settings.gif_palette_speed.unwrap().clamp(1, 30),
+    };
+    let width = params.width;
+    let job = Job::new(format!("Saving animation as {}", path.display()), frames.len(), sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let render_job = job.clone();
+            let total = frames.len();
+            let images = parallel::ordered_map(frames.into_iter(), threads, move |frame| {
+                if render_job.is_cancelled() {
+                    return Err("cancelled".to_string());
+                }
+                let image = animation::render(&make_svg(&data, frame, &options).to_string(), width);
+                render_job.done();
+                image
+            });
+            let images = images.map(|image| image.and_then(|image| image));
+            if let Err(e) = animation::save(format, &path.to_string_lossy(), images, total, &params) {
+                job.fail(e);
+                // an unfinished animation is not kept
+                remove_file(&path);
+            }
+            remove_if_cancelled(&job, &path);
+            job.finish()
+        }),
+    ))
+}
+
+pub fn export_animated_svg(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    let frames = export_frames(data)?;
+    let path = match output_path(data, &format!("{}.svg", data.export.name), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+
+    let settings = get_settings();
+    let threads = settings.max_threads.unwrap();
+    let job = Job::new(format!("Saving animation as {}", path.display()), frames.len(), sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let mut animation = AnimatedSvg::new(data.header.fps_speed);
+            let canvas = svg_canvas(&data.header, &options);
+            let total = frames.len();
+            let objects = {
+                let data = data.clone();
+                parallel::ordered_map(frames.into_iter(), threads, move |frame| make_svg_objects(&data, frame, &options))
+            };
+            if push_all(&job, objects, total, |objects| animation.push_frame(objects)) {
+                if let Err(e) = animation.save(canvas, &path.to_string_lossy()) {
+                    job.fail(format!("Can't save {}, {}", path.display(), e));
+                }
+            }
+            job.finish()
+        }),
+    ))
+}
+
+pub fn export_html(data: &AppData, options: ExportOptions, sink: Option<ExtEventSink>) -> Export {
+    let frames = export_frames(data)?;
+    let path = match output_path(data, &format!("{}.html", data.export.name), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+
+    let threads = get_settings().max_threads.unwrap();
+    let job = Job::new(format!("Saving replay as {}", path.display()), frames.len(), sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let total = frames.len();
+            let objects = {
+                let frames_data = data.frames.clone();
+                parallel::ordered_map(frames.into_iter(), threads, move |frame| {
+                    html_export::frame_objects(&frames_data.get(frame).unwrap())
+                })
+            };
+            if let Err(e) = html_export::save(&path.to_string_lossy(), &data.header, &data.tags, &options, objects, total, &job) {
+                job.fail(format!("Can't save {}, {}", path.display(), e));
+            }
+            job.finish()
+        }),
+    ))
+}
+
+pub fn export_contact_sheet(data: &AppData, options: ExportOptions, extension: &str, sink: Option<ExtEventSink>) -> Export {
+    let frames = export_frames(data)?;
+    let path = match output_path(data, &format!("{}_sheet.{}", data.export.name, extension), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+
+    let settings = get_settings();
+    let threads = settings.max_threads.unwrap();
+    let grid = contact_sheet::Grid::new(
+        settings.sheet_columns.unwrap(),
+        settings.sheet_cell_width.unwrap(),
+        options.region(&data.header),
+    );
+    // explicitly chosen ticks are shown as ticks
+    let by_ticks = !data.export.ticks.trim().is_empty();
+    let png = extension == "png";
+    let job = Job::new(format!("Saving contact sheet as {}", path.display()), frames.len(), sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let mut sheet = ContactSheet::new(grid, options.theme.text.clone());
+            let background = options.background(&data.header);
+            let total = frames.len();
+            let cells = parallel::ordered_map(frames.into_iter().enumerate(), threads, move |(ind, frame)| {
+                let cell = grid.cell(ind);
+                let img = make_svg(&data, frame, &options)
+                    .set("x", cell.x0)
+                    .set("y", cell.y0)
+                    .set("width", cell.width())
+                    .set("height", cell.height());
+                let caption = match data.frames.get(frame) {
+                    Some(frame) if by_ticks => format!("tick {}", frame.tick + 1),
+                    _ => format!("frame {}", frame + 1),
+                };
+                (img.to_string(), caption)
+            });
+            if push_all(&job, cells, total, |(img, caption)| sheet.push(&img, &caption)) {
+                let (svg, width) = sheet.finish(background);
+                let file_name = path.to_string_lossy();
+                let result = if png {
+                    raster::save_png(&svg, width, &file_name)
+                } else {
+                    fs::write(&path, svg).map_err(|e| e.to_string())
+                };
+                if let Err(e) = result {
+                    job.fail(format!("Can't save {}, {}", file_name, e));
+                }
+            }
+            job.finish()
+        }),
+    ))
+}
+
+pub fn make_video_from_frames(data: &AppData, sink: Option<ExtEventSink>) -> Export {
+    let frames = export_frames(data)?;
+    // png frames saved before with the same settings
+    let pngs = frames
+        .into_iter()
+        .map(|frame| frame_file_name(data, frame, "png"))
+        .filter(|file_name| Path::new(&data.export.output_dir).join(file_name).is_file())
+        .collect::<Vec<_>>();
+    if pngs.is_empty() {
+        eprintln!("No png frames in folder {}, save them first", data.export.output_dir);
+        return None;
+    }
+    let video = match output_path(data, &format!("{}.mp4", data.export.name), &sink) {
+        Ok(path) => path,
+        Err(export) => return export,
+    };
+    let list = temp_path("frames.txt");
+
+    // the list is in another folder, so paths are absolute, the last one is repeated to be shown for its duration
+    let fps = data.header.fps_speed;
+    let mut content = String::new();
+    for file_name in pngs.iter().chain(pngs.last()) {
+        let path = Path::new(&data.export.output_dir).join(file_name);
+        let path = std::path::absolute(&path).unwrap_or(path);
+        content += &format!("file '{}'\nduration {}\n", path.to_string_lossy().replace('\'', "'\\''"), fps);
+    }
+    if let Err(e) = fs::write(&list, content) {
+        eprintln!("Can't save {}, {}", list.display(), e);
+        return None;
+    }
+
+    let job = Job::new(format!("Saving video as {}", video.display()), 1, sink);
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let list_file = list.to_string_lossy();
+            let video_file = video.to_string_lossy();
+            let rate = (1. / fps).to_string();
+            let args = [
+                "ffmpeg",
+                "-y",
+                "-f",
+                "concat",
+                "-safe",
+                "0",
+                "-i",
+                &list_file,
+                "-r",
+                &rate,
+                "-c:v",
+                "libx264",
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+                &video_file,
+            ];
+            match external::run(&args, None, &job).and_then(|status| external::check("ffmpeg", status.unwrap())) {
+                Ok(()) => job.done(),
+                Err(e) => job.fail(e),
+            }
+            if let Err(e) = fs::remove_file(&list) {
+                job.fail(format!("Can't remove {}, {}", list.display(), e));
+            }
+            remove_if_cancelled(&job, &video);
+            job.finish()
+        }),
+    ))
+}
+
+// viewport with the background
+fn svg_canvas(header: &Header, options: &ExportOptions) -> Document {
+    let region = options.region(header);
+    let img = Document::new()
+        .set("viewBox", (region.x0, region.y0, region.width(), region.height()))
+        .set("width", region.width())
+        .set("height", region.height());
+    match options.background(header) {
+        Some(color) => {
+            let rect = SvgRect::new()
+                .set("x", region.x0)
+                .set("y", region.y0)
+                .set("width", region.width())
+                .set("height", region.height())
+                .set("fill", overlay::color_to_string(&color))
+                .set("opacity", color.as_rgba().3);
+            img.add(rect)
+        }
+        None => img,
+    }
+}
+
+// frames are saved independently of each other on a pool of threads, from a snapshot of the data
+fn save_frames(
+    data: &AppData,
+    extension: &'static str,
+    sink: Option<ExtEventSink>,
+    save: impl Fn(&AppData, usize, &Path, &Job) -> Result<(), String> + Send + Sync + 'static,
+) -> Export {
+    let frames = export_frames(data)?;
+    let threads = get_settings().max_threads.unwrap();
+    let frames = frame_paths(data, frames, extension);
+    let job = Job::new(format!("Saving frames to {}", data.export.output_dir), frames.len(), sink);
+    let data = data.clone();
+    Some((
+        job.status(),
+        thread::spawn(move || {
+            let pool = ThreadPool::new(threads);
+            let data = Arc::new(data);
+            let save = Arc::new(save);
+            for (frame, path) in frames {
+                let path = match path {
+                    Ok(path) => path,
+                    Err(reason) => {
+                        report_no_path(&job, reason);
+                        continue;
+                    }
+                };
+                let data = data.clone();
+                let save = save.clone();
+                let job = job.clone();
+                pool.execute(move || {
+                    if !job.is_cancelled() {
+                        report_frame(&job, frame, save(&data, frame, &path, &job));
+                    }
+                });
+            }
+            pool.join();
+            job.finish()
+        }),
+    ))
+}
+
+fn save_svg(data: &AppData, frame: usize, options: &ExportOptions, path: &Path) -> Result<(), String> {
+    svg::save(path, &make_svg(data, frame, options)).map_err(|e| format!("Can't save {}, {}", path.display(), e))
+}
+
+// with an external converter the svg is saved to a temporary file until it is converted
+fn save_png(svg: &str, width: u32, path: &Path, converter: Option<&Converter>, job: &Job) -> Result<(), String> {
+    let png_file = path.to_string_lossy();
+    let converter = match converter {
+        Some(converter) => converter,
+        None => return raster::save_png(svg, width, &png_file),
+    };
+    let svg_path = temp_path("frame.svg");
+    fs::write(&svg_path, svg).map_err(|e| format!("Can't save {}, {}", svg_path.display(), e))?;
+    let result = converter.convert(&svg_path.to_string_lossy(), &png_file, width, job);
+    fs::remove_file(&svg_path).map_err(|e| format!("Can't remove {}, {}", svg_path.display(), e))?;
+    result
+}
+
+// in the temporary folder, unique for each call, so that no file of the user is replaced
+fn temp_path(file_name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("rviewer_{}_{}_{}", process::id(), n, file_name))
+}
+
+fn report_frame(job: &Job, frame: usize, result: Result<(), String>) {
+    match result {
+        Ok(()) => job.done(),
+        Err(e) => job.fail(format!("frame {}: {}", frame + 1, e)),
+    }
+}
+
+// a file made by a cancelled job is incomplete
+fn remove_if_cancelled(job: &Job, path: &Path) {
+    if job.is_cancelled() {
+        remove_file(path);
+    }
+}
+
+fn remove_file(path: &Path) {
+    if path.is_file() {
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("Can't remove {}, {}", path.display(), e);
+        }
+    }
+}
+
+// passes frames made in parallel on in their order, returns if all of them came and the job goes on
+fn push_all<R>(job: &Job, results: impl Iterator<Item = Result<R, String>>, total: usize, mut push: impl FnMut(R)) -> bool {
+    let mut pushed = 0;
+    for result in results {
+        if job.is_cancelled() {
+            return false;
+        }
+        match result {
+            Ok(result) => push(result),
+            Err(e) => {
+                job.fail(format!("Can't make frame {}, {}", pushed + 1, e));
+                return false;
+            }
+        }
+        pushed += 1;
+        job.done();
+    }
+    if pushed < total {
+        job.fail(format!("Only {} of {} frames were made", pushed, total));
+        return false;
+    }
+    !job.is_cancelled()
+}
+
+// files of the frames, or why they are not saved
+fn frame_paths(data: &AppData, frames: Vec<usize>, extension: &str) -> Vec<(usize, Result<PathBuf, NoPath>)> {
+    frames
+        .into_iter()
+        .map(|frame| (frame, data.export.path(&frame_file_name(data, frame, extension))))
+        .collect()
+}
+
+// the path to save to, or an export that only reports why there is none
+fn output_path(data: &AppData, file_name: &str, sink: &Option<ExtEventSink>) -> Result<PathBuf, Export> {
+    data.export.path(file_name).map_err(|reason| {
+        let job = Job::new(format!("Saving {}", file_name), 1, sink.clone());
+        report_no_path(&job, reason);
+        Some((job.status(), thread::spawn(move || job.finish())))
+    })
+}
+
+fn report_no_path(job: &Job, reason: NoPath) {
+    match reason {
+        NoPath::Skipped(path) => job.skip(path.display().to_string()),
+        NoPath::Error(e) => job.fail(e),
+    }
+}
+
+// frames chosen in the export settings, `None` if there are none
+fn export_frames(data: &AppData) -> Option<Vec<usize>> {
+    match data.export.selected_frames(&data.frames) {
+        Ok(frames) if frames.is_empty() => {
+            eprintln!("No frames to export");
+            None
+        }
+        Ok(frames) => Some(frames),
+        Err(e) => {
+            eprintln!("Can't choose frames to export, {}", e);
+            None
+        }
+    }
+}
+
+fn frame_file_name(data: &AppData, frame: usize, extension: &str) -> String {
+    let tick = data.frames.get(frame).map_or(0, |frame| frame.tick);
+    data.export.file_name(frame, tick, extension)
+}
+
+fn enabled_tags(data: &AppData) -> HashSet<String> {
+    data.tags.iter().filter(|(_, b)| *b).map(|(tag, _)| tag.clone()).collect()
+}
+
+fn need_to_export(item: &Object, enabled_tags: &HashSet<String>, options: &ExportOptions) -> bool {
+    item.need_to_draw(enabled_tags) && (options.messages || !item.as_any().is::<MMessage>())
+}
+
+fn make_svg(data: &AppData, frame_ind: usize, options: &ExportOptions) -> Document {
+    let header = &data.header;
+    let enabled_tags = enabled_tags(data);
+    let frame = data.frames.get(frame_ind).unwrap();
+
+    let transform = |p| header.svg_transform(p);
+    let params = SvgParams {
+        size: header.size,
+        width_scale: header.svg_width_scale,
+        flipy: header.flipy,
+        transform: &transform,
+        viewport: options.region(header),
+        overlay_font: overlay::font_size(options.region(header)),
+        overlay_color: options.theme.text.clone(),
+    };
+
+    let mut img = svg_canvas(header, options);
+    for item in frame.objects.iter() {
+        if need_to_export(item, &enabled_tags, options) {
+            img = item.draw_on_image(img, &params);
+        }
+    }
+    if options.legend {
+        img = overlay::legend(img, options.region(header), &options.theme.text, frame_ind, data.frames.len(), &data.tags);
+    }
+
+    img
+}
+
+// each object separately, so that it can be found in other frames
+fn make_svg_objects(data: &AppData, frame_ind: usize, options: &ExportOptions) -> Vec<(Option<i32>, String)> {
+    let header = &data.header;
+    let enabled_tags = enabled_tags(data);
+    let frame = data.frames.get(frame_ind).unwrap();
+
+    let transform = |p| header.svg_transform(p);
+    let params = SvgParams {
+        size: header.size,
+        width_scale: header.svg_width_scale,
+        flipy: header.flipy,
+        transform: &transform,
+        viewport: options.region(header),
+        overlay_font: overlay::font_size(options.region(header)),
+        overlay_color: options.theme.text.clone(),
+    };
+
+    let mut objects = frame
+        .objects
+        .iter()
+        .filter(|item| need_to_export(item, &enabled_tags, options))
+        .map(|item| {
+            (
+                item.common().id,
+                animated_svg::object_markup(&item.draw_on_image(Document::new(), &params)),
+            )
+        })
+        .collect::<Vec<_>>();
+    if options.legend {
+        let legend = overlay::legend(
+            Document::new(),
+            options.region(header),
+            &options.theme.text,
+            frame_ind,
+            data.frames.len(),
+            &data.tags,
+        );
+        objects.push((None, animated_svg::object_markup(&legend)));
+    }
+    objects
+}
@@ -0,0 +1,189 @@
+use crate::replay::Frames;
+use crate::settings::Settings;
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+/// What to do with a file that is already in the output folder.
+#[derive(Clone, Copy, Data, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overwrite {
+    // a number is added to the name of the new file
+    Rename,
+    Skip,
+    Replace,
+}
+
+/// Why a file is not saved.
+pub enum NoPath {
+    // the file exists and the `Skip` policy leaves it as it is
+    Skipped(PathBuf),
+    Error(String),
+}
+
+/// Where exported files are saved and which frames are exported.
+#[derive(Clone, Data, Lens)]
+pub struct ExportConfig {
+    pub output_dir: String,
+    pub file_template: String,
+    pub frames: String,
+    pub ticks: String,
+    pub every: usize,
+    pub overwrite: Overwrite,
+    // of the replay file without extension, for {name} in templates
+    pub name: String,
+}
+
+impl ExportConfig {
+    pub fn new(settings: &Settings, name: &str) -> Self {
+        Self {
+            output_dir: settings.output_dir.clone().unwrap(),
+            file_template: settings.file_template.clone().unwrap(),
+            frames: settings.export_frames.clone().unwrap(),
+            ticks: settings.export_ticks.clone().unwrap(),
+            every: settings.export_every.unwrap(),
+            overwrite: settings.overwrite.unwrap(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn save_to(&self, settings: &mut Settings) {
+        settings.output_dir = Some(self.output_dir.clone());
+        settings.file_template = Some(self.file_template.clone());
+        settings.export_frames = Some(self.frames.clone());
+        settings.export_ticks = Some(self.ticks.clone());
+        settings.export_every = Some(self.every);
+        settings.overwrite = Some(self.overwrite);
+    }
+
+    /// Sets a value from a command line option like `--frames 10..50`.
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "out" => self.output_dir = value.to_string(),
+            "template" => self.file_template = value.to_string(),
+            "frames" => {
+                parse_list(value)?;
+                self.frames = value.to_string();
+            }
+            "ticks" => {
+                parse_list(value)?;
+                self.ticks = value.to_string();
+            }
+            "every" => self.every = value.parse().map_err(|_| format!("bad number \"{}\"", value))?,
+            "overwrite" => {
+                self.overwrite = serde_json::from_value(serde_json::Value::String(value.to_string()))
+                    .map_err(|_| format!("expected rename, skip or replace, got \"{}\"", value))?
+            }
+            _ => return Err(format!("unknown option \"{}\"", key)),
+        }
+        Ok(())
+    }
+
+    /// Indices of the frames to export, frames and ticks selections both apply.
+    pub fn selected_frames(&self, frames: &Frames) -> Result<Vec<usize>, String> {
+        let frame_ranges = parse_ranges(&self.frames)?;
+        let tick_ranges = parse_list(&self.ticks)?
+            .into_iter()
+            .map(|part| match part {
+                Part::Range(ticks) => frames.first_frame_of_tick(ticks.start)..frames.first_frame_of_tick(ticks.end),
+                // the frame of the tick itself, after in-betweens
+                Part::Single(tick) => match frames.first_frame_of_tick(tick + 1) {
+                    end if frames.first_frame_of_tick(tick) < end => end - 1..end,
+                    _ => 0..0,
+                },
+            })
+            .collect::<Vec<_>>();
+        let selected = |ranges: &[Range<usize>], frame| ranges.is_empty() || ranges.iter().any(|range| range.contains(&frame));
+        Ok((0..frames.len())
+            .filter(|&frame| selected(&frame_ranges, frame) && selected(&tick_ranges, frame))
+            .step_by(self.every.max(1))
+            .collect())
+    }
+
+    pub fn file_name(&self, frame: usize, tick: usize, extension: &str) -> String {
+        let mut name = String::new();
+        let mut rest = self.file_template.as_str();
+        while let Some(start) = rest.find('{') {
+            name.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let (key, format) = rest[1..end].split_once(':').unwrap_or((&rest[1..end], ""));
+            let value = match key {
+                "name" => self.name.clone(),
+                "frame" => (frame + 1).to_string(),
+                "tick" => (tick + 1).to_string(),
+                _ => rest[..=end].to_string(),
+            };
+            let width = format.parse().unwrap_or(0);
+            name.push_str(&format!("{:0>width$}", value, width = width));
+            rest = &rest[end + 1..];
+        }
+        name.push_str(rest);
+        format!("{}.{}", name, extension)
+    }
+
+    /// Path to save a file to, the output folder is created if needed.
+    pub fn path(&self, file_name: &str) -> Result<PathBuf, NoPath> {
+        let dir = Path::new(&self.output_dir);
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(NoPath::Error(format!("Can't create folder {}, {}", dir.display(), e)));
+        }
+        let path = dir.join(file_name);
+        if !path.exists() || self.overwrite == Overwrite::Replace {
+            return Ok(path);
+        }
+        if self.overwrite == Overwrite::Skip {
+            return Err(NoPath::Skipped(path));
+        }
+        let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+        let extension = path
+            .extension()
+            .map_or(String::new(), |extension| format!(".{}", extension.to_string_lossy()));
+        Ok((1..)
+            .map(|n| dir.join(format!("{}_{}{}", stem, n, extension)))
+            .find(|path| !path.exists())
+            .unwrap())
+    }
+}
+
+enum Part {
+    Range(Range<usize>),
+    Single(usize),
+}
+
+// comma separated numbers and ranges "first..last", starting from 1, range ends are inclusive and optional,
+// empty if everything is selected
+fn parse_list(s: &str) -> Result<Vec<Part>, String> {
+    let parse = |x: &str, default| match x.trim() {
+        "" => Ok(default),
+        x => match x.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("bad number \"{}\" in \"{}\"", x, s)),
+        },
+    };
+    s.split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| match part.split_once("..") {
+            Some((first, last)) => Ok(Part::Range(parse(first, 1)? - 1..parse(last, usize::MAX)?)),
+            None => Ok(Part::Single(parse(part, 1)? - 1)),
+        })
+        .collect()
+}
+
+/// A list like "1,50,100..120" as ranges starting from 0, empty if everything is selected.
+pub fn parse_ranges(s: &str) -> Result<Vec<Range<usize>>, String> {
+    Ok(parse_list(s)?
+        .into_iter()
+        .map(|part| match part {
+            Part::Range(range) => range,
+            Part::Single(ind) => ind..ind + 1,
+        })
+        .collect())
+}
@@ -0,0 +1,58 @@
+use crate::export_config::{ExportConfig, Overwrite};
+use crate::settings::{save_settings, Settings};
+
+use druid::text::ParseFormatter;
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, RadioGroup, SizedBox, TextBox};
+use druid::{Widget, WidgetExt};
+
+const PADDING: f64 = 8.0;
+const LABEL_WIDTH: f64 = 110.0;
+
+/// Window to edit where exported files go and which frames are exported.
+/// Changes apply to the next export, "Save" also keeps them in the user config (`$XDG_CONFIG_HOME/rviewer/config.json`).
+pub fn make_export_dialog() -> impl Widget<ExportConfig> {
+    Flex::column()
+        .with_child(row("Output folder", TextBox::new().lens(ExportConfig::output_dir)))
+        .with_spacer(PADDING)
+        .with_child(row(
+            "File name",
+            TextBox::new().with_placeholder("{name}_{frame:05}").lens(ExportConfig::file_template),
+        ))
+        .with_spacer(PADDING)
+        .with_child(row(
+            "Frames",
+            TextBox::new().with_placeholder("all, or 10..50").lens(ExportConfig::frames),
+        ))
+        .with_spacer(PADDING)
+        .with_child(row("Ticks", TextBox::new().with_placeholder("all, or 10..50").lens(ExportConfig::ticks)))
+        .with_spacer(PADDING)
+        .with_child(row(
+            "Every nth frame",
+            TextBox::new().with_formatter(ParseFormatter::new()).lens(ExportConfig::every),
+        ))
+        .with_spacer(PADDING)
+        .with_child(row(
+            "Existing files",
+            RadioGroup::row(vec![
+                ("rename", Overwrite::Rename),
+                ("skip", Overwrite::Skip),
+                ("replace", Overwrite::Replace),
+            ])
+            .lens(ExportConfig::overwrite),
+        ))
+        .with_spacer(PADDING)
+        .with_child(Button::new("Save").on_click(|_ctx, config: &mut ExportConfig, _env| {
+            let mut settings = Settings::default();
+            config.save_to(&mut settings);
+            save_settings(&settings);
+        }))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .padding(PADDING)
+}
+
+fn row(label: &str, widget: impl Widget<ExportConfig> + 'static) -> impl Widget<ExportConfig> {
+    let label = label.to_string();
+    Flex::row()
+        .with_child(SizedBox::new(Label::new(move |_: &ExportConfig, _: &_| label.clone())).width(LABEL_WIDTH))
+        .with_flex_child(widget.expand_width(), 1.0)
+}
@@ -0,0 +1,55 @@
+use crate::replay::Header;
+use crate::settings::Settings;
+use crate::theme::Theme;
+
+use druid::{Color, Rect};
+
+/// How frames are exported: what is drawn under and over them, and which part of them.
+#[derive(Clone)]
+pub struct ExportOptions {
+    pub messages: bool,
+    pub legend: bool,
+    pub transparent: bool,
+    pub theme: Theme,
+    // in image coordinates, the whole image if not set
+    pub region: Option<Rect>,
+    // pixels per unit of the image, used instead of a fixed width
+    pub scale: Option<f64>,
+    // width of raster images in pixels, overrides both the scale and the resolution settings
+    pub width: Option<u32>,
+}
+
+impl ExportOptions {
+    pub fn new(settings: &Settings, region: Option<Rect>) -> Self {
+        Self {
+            messages: settings.export_messages.unwrap(),
+            legend: settings.export_legend.unwrap(),
+            transparent: settings.transparent_background.unwrap(),
+            theme: Theme::from_settings(settings),
+            region,
+            scale: settings.export_scale,
+            width: None,
+        }
+    }
+
+    pub fn background(&self, header: &Header) -> Option<Color> {
+        if self.transparent {
+            None
+        } else {
+            Some(header.background.clone().unwrap_or_else(|| self.theme.canvas.clone()))
+        }
+    }
+
+    pub fn region(&self, header: &Header) -> Rect {
+        self.region.unwrap_or_else(|| header.size.to_rect())
+    }
+
+    /// Width of raster images in pixels, `resolution` is used if there is no scale.
+    pub fn width(&self, header: &Header, resolution: usize) -> u32 {
+        match (self.width, self.scale) {
+            (Some(width), _) => width,
+            (None, Some(scale)) => (self.region(header).width() * scale).round().max(1.0) as u32,
+            (None, None) => resolution as u32,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::job::Job;
+use crate::settings::Settings;
+
+use std::time::{Duration, Instant};
+
+use subprocess::{ExitStatus, Popen, PopenConfig, Redirection};
+
+// how often a running process checks if the job is cancelled
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Svg to png conversion with `rsvg-convert` or `inkscape`.
+#[derive(Clone)]
+pub struct Converter {
+    program: String,
+    inkscape: bool,
+    // of the first attempt, each next one gets this much more
+    timeout: Duration,
+    attempts: u64,
+}
+
+impl Converter {
+    /// `None` if images are rendered by `rviewer` itself.
+    pub fn new(settings: &Settings) -> Option<Self> {
+        let (program, inkscape) = match settings.conversion_tool.as_deref().unwrap() {
+            "builtin" => return None,
+            "rsvg-convert" => ("rsvg-convert".to_string(), false),
+            _ => (settings.inkscape_path.clone().unwrap(), true),
+        };
+        Some(Self {
+            program,
+            inkscape,
+            timeout: Duration::from_secs(settings.conversion_timeout.unwrap()),
+            attempts: settings.conversion_attempts.unwrap().max(1),
+        })
+    }
+
+    pub fn convert(&self, svg_file: &str, png_file: &str, width: u32, job: &Job) -> Result<(), String> {
+        let width = width.to_string();
+        let args = if self.inkscape {
+            vec![self.program.as_str(), "-o", png_file, "-w", &width, svg_file]
+        } else {
+            vec![self.program.as_str(), svg_file, "-o", png_file, "-w", &width]
+        };
+        for attempt in 1..=self.attempts {
+            if let Some(status) = run(&args, Some(self.timeout * attempt as u32), job)? {
+                return check(&self.program, status);
+            }
+        }
+        Err(format!("{} timed out {} times", self.program, self.attempts))
+    }
+}
+
+pub fn check(program: &str, status: ExitStatus) -> Result<(), String> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed, {:?}", program, status))
+    }
+}
+
+/// Runs a program until it exits, `None` if it is stopped after `timeout`.
+pub fn run(args: &[&str], timeout: Option<Duration>, job: &Job) -> Result<Option<ExitStatus>, String> {
+    // output is not read, so it is left to the console, otherwise a full pipe would block the process
+    let config = PopenConfig {
+        stdin: Redirection::Pipe,
+        ..Default::default()
+    };
+    let mut p = Popen::create(args, config).map_err(|e| format!("Can't run {}, {}", args[0], e))?;
+    let start = Instant::now();
+    loop {
+        if let Some(status) = p.wait_timeout(POLL_INTERVAL).map_err(|e| e.to_string())? {
+            return Ok(Some(status));
+        }
+        let cancelled = job.is_cancelled();
+        if cancelled || timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            p.terminate().map_err(|e| e.to_string())?;
+            p.wait().map_err(|e| e.to_string())?;
+            return if cancelled { Err("cancelled".to_string()) } else { Ok(None) };
+        }
+    }
+}
@@ -4,10 +4,13 @@ use crate::svg_params::SvgParams;
 use crate::transform::Transform;
 
 use druid::widget::prelude::*;
+use druid::Rect;
 
 use std::any::Any;
 use std::collections::HashSet;
+use std::sync::Arc;
 
+use serde_json::Value;
 use svg::Document;
 
 pub mod rect;
@@ -27,10 +30,18 @@ pub use message::MMessage;
 pub mod common;
 pub use common::CommonParams;
 
-pub trait Figure {
-    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform);
+pub type Object = Arc<dyn Figure>;
+
+pub trait Figure: Send + Sync {
+    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform, env: &Env);
     fn draw_on_image(&self, img: Document, params: &SvgParams) -> Document;
+    // bounding box in input coordinates, None if the figure is not attached to them
+    fn bounds(&self) -> Option<Rect>;
     fn common(&self) -> &CommonParams;
+    // plain representation for the html player
+    fn to_json(&self) -> Value;
+    // line of the replay file, parsed back to the same figure
+    fn to_line(&self) -> String;
     fn as_any(&self) -> &dyn Any;
 
     fn tags(&self) -> &Vec<String> {
@@ -51,38 +62,60 @@ pub trait Figure {
     }
 }
 
-pub fn from_string(s: &str, draw_properties: &mut DrawProperties) -> Option<Box<dyn Figure + Send>> {
+pub fn from_string(s: &str, draw_properties: &mut DrawProperties) -> Option<Object> {
     if s.starts_with("rect") {
-        Some(Box::new(MRect::from_string(s, draw_properties)))
+        Some(Arc::new(MRect::from_string(s, draw_properties)))
     } else if s.starts_with("circle") {
-        Some(Box::new(MCircle::from_string(s, draw_properties)))
+        Some(Arc::new(MCircle::from_string(s, draw_properties)))
     } else if s.starts_with("line") {
-        Some(Box::new(MLine::from_string(s, draw_properties)))
+        Some(Arc::new(MLine::from_string(s, draw_properties)))
     } else if s.starts_with("grid") {
-        Some(Box::new(MGrid::from_string(s, draw_properties)))
+        Some(Arc::new(MGrid::from_string(s, draw_properties)))
     } else if s.starts_with("poly") {
-        Some(Box::new(MPoly::from_string(s, draw_properties)))
+        Some(Arc::new(MPoly::from_string(s, draw_properties)))
     } else if s.starts_with("text") {
-        Some(Box::new(MText::from_string(s, draw_properties)))
+        MText::from_string(s, draw_properties).map(|x| Arc::new(x) as Object)
     } else if s.starts_with("msg") {
-        Some(Box::new(MMessage::from_string(s, draw_properties)))
+        Some(Arc::new(MMessage::from_string(s, draw_properties)))
     } else {
         None
     }
 }
 
+/// Name of the figure as in the replay file.
+pub fn figure_name(item: &Object) -> &'static str {
+    let item = item.as_any();
+    if item.is::<MRect>() {
+        "rect"
+    } else if item.is::<MCircle>() {
+        "circle"
+    } else if item.is::<MLine>() {
+        "line"
+    } else if item.is::<MGrid>() {
+        "grid"
+    } else if item.is::<MPoly>() {
+        "poly"
+    } else if item.is::<MText>() {
+        "text"
+    } else if item.is::<MMessage>() {
+        "msg"
+    } else {
+        "unknown"
+    }
+}
+
 macro_rules! in_betweens_match {
     ($a:ident, $b:ident, $in_between_properties:ident, $t:ty) => {
         if let Some(a) = $a.as_any().downcast_ref::<$t>() {
             return <$t>::in_betweens(&a, $b.as_any().downcast_ref::<$t>().unwrap(), $in_between_properties)
                 .into_iter()
-                .map(|x| Box::new(x) as Box<dyn Figure + Send>)
+                .map(|x| Arc::new(x) as Object)
                 .collect();
         }
     };
 }
 
-pub fn in_betweens(a: &dyn Figure, b: &dyn Figure, in_between_properties: &InBetweenProperties) -> Vec<Box<dyn Figure + Send>> {
+pub fn in_betweens(a: &dyn Figure, b: &dyn Figure, in_between_properties: &InBetweenProperties) -> Vec<Object> {
     in_betweens_match!(a, b, in_between_properties, MRect);
     in_betweens_match!(a, b, in_between_properties, MCircle);
     in_betweens_match!(a, b, in_between_properties, MLine);
@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -10,7 +11,9 @@ use std::f64::consts::PI;
 
 use druid::kurbo::Circle;
 use druid::widget::prelude::*;
-use druid::Point;
+use druid::{Point, Rect};
+
+use serde_json::{json, Value};
 
 use svg::node::element::{path::Data, Circle as SvgCircle, Path};
 use svg::Document;
@@ -83,7 +86,7 @@ impl MCircle {
 }
 
 impl Figure for MCircle {
-    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform) {
+    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform, _env: &Env) {
         let center = transform.point(self.center);
         let mut r = self.radius;
         r *= scale;
@@ -172,10 +175,36 @@ impl Figure for MCircle {
         }
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(self.center, Size::new(self.radius * 2.0, self.radius * 2.0)).abs())
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "circle",
+            "c": point_to_json(self.center),
+            "r": self.radius,
+            "f": self.fill,
+            "w": self.width,
+            "arc": self.arc.map(|(fr, to)| [fr, to]),
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!("circle c={} r={} w={}", point_to_line(self.center), self.radius, self.width);
+        if self.fill {
+            line += " f=1";
+        }
+        if let Some((fr, to)) = self.arc {
+            line += &format!(" arc=({},{})", fr, to);
+        }
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,7 +1,9 @@
 use crate::app_data::DrawProperties;
 use crate::parse::Params;
 
-use druid::Color;
+use druid::{Color, Point};
+
+use serde_json::{json, Value};
 
 #[derive(Clone)]
 pub struct CommonParams {
@@ -13,14 +15,66 @@ pub struct CommonParams {
 }
 
 impl CommonParams {
-    pub fn new(params: &Params, _draw_properties: &mut DrawProperties) -> Self {
-        Self {
+    pub fn new(params: &Params, draw_properties: &mut DrawProperties) -> Self {
+        let mut common = Self {
             color: params.get("col").unwrap_or(Color::rgb8(0 as u8, 0 as u8, 0 as u8)),
             tags: params.get("t").unwrap_or(Vec::new()),
             keep: params.get("k").unwrap_or(false),
             id: params.get("id"),
             func: params.get("fu"),
+        };
+        if let Some(layer) = &draw_properties.layer {
+            layer.apply(&mut common);
+        }
+        common
+    }
+
+    /// Adds color and tags to the json of a figure.
+    pub fn with_json(&self, mut value: Value) -> Value {
+        let (r, g, b, a) = self.color.as_rgba8();
+        value["col"] = json!([r, g, b, a]);
+        if !self.tags.is_empty() {
+            value["t"] = json!(self.tags);
+        }
+        value
+    }
+
+    /// Adds color, tags and the rest of the common params to the replay line of a figure.
+    pub fn with_line(&self, mut line: String) -> String {
+        let (r, g, b, a) = self.color.as_rgba8();
+        if a == 255 {
+            line += &format!(" col=({},{},{})", r, g, b);
+        } else {
+            line += &format!(" col=({},{},{},{})", r, g, b, a);
+        }
+        for tag in self.tags.iter() {
+            line += &format!(" t={}", tag);
+        }
+        if self.keep {
+            line += " k=1";
         }
+        if let Some(id) = self.id {
+            line += &format!(" id={}", id);
+        }
+        if let Some(func) = &self.func {
+            line += &format!(" fu={}", func);
+        }
+        line
+    }
+}
+
+pub fn point_to_json(p: Point) -> Value {
+    json!([p.x, p.y])
+}
+
+pub fn point_to_line(p: Point) -> String {
+    format!("({},{})", p.x, p.y)
+}
+
+pub fn alignment_to_line(alignment: (char, char)) -> String {
+    match alignment {
+        ('C', 'C') => String::new(),
+        (x, y) => format!(" a={}{}", x, y),
     }
 }
 
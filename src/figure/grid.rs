@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{alignment_to_line, point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -9,7 +10,9 @@ use std::any::Any;
 
 use druid::kurbo::Line;
 use druid::widget::prelude::*;
-use druid::Point;
+use druid::{Point, Rect};
+
+use serde_json::{json, Value};
 
 use svg::node::element::Line as SvgLine;
 use svg::Document;
@@ -55,10 +58,8 @@ impl MGrid {
             })
             .collect()
     }
-}
 
-impl Figure for MGrid {
-    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform) {
+    fn aligned_center(&self) -> Point {
         let mut center = self.center;
         if self.alignment.0 == 'B' {
             center.x += self.size.x / 2.;
@@ -70,7 +71,13 @@ impl Figure for MGrid {
         } else if self.alignment.1 == 'E' {
             center.y -= self.size.y / 2.;
         }
-        let center = transform.point(center);
+        center
+    }
+}
+
+impl Figure for MGrid {
+    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform, _env: &Env) {
+        let center = transform.point(self.aligned_center());
         let mut size = self.size;
         size.x *= scale;
         size.y *= scale;
@@ -98,18 +105,7 @@ impl Figure for MGrid {
     }
 
     fn draw_on_image(&self, mut img: Document, params: &SvgParams) -> Document {
-        let mut center = self.center.clone();
-        if self.alignment.0 == 'B' {
-            center.x += self.size.x / 2.;
-        } else if self.alignment.0 == 'E' {
-            center.x -= self.size.x / 2.;
-        }
-        if self.alignment.1 == 'B' {
-            center.y += self.size.y / 2.;
-        } else if self.alignment.1 == 'E' {
-            center.y -= self.size.y / 2.;
-        }
-        let center = (params.transform)(center);
+        let center = (params.transform)(self.aligned_center());
         for i in 0..self.dims.0 + 1 {
             let line = SvgLine::new()
                 .set("x1", center.x - self.size.x / 2. + self.size.x / self.dims.0 as f64 * i as f64)
@@ -142,10 +138,38 @@ impl Figure for MGrid {
         img
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(self.aligned_center(), Size::new(self.size.x, self.size.y)).abs())
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "grid",
+            "c": point_to_json(self.center),
+            "s": point_to_json(self.size),
+            "d": [self.dims.0, self.dims.1],
+            "w": self.width,
+            "a": format!("{}{}", self.alignment.0, self.alignment.1),
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!(
+            "grid c={} s={} d=({},{}) w={}",
+            point_to_line(self.center),
+            point_to_line(self.size),
+            self.dims.0,
+            self.dims.1,
+            self.width
+        );
+        line += &alignment_to_line(self.alignment);
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
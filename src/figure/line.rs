@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -9,7 +10,9 @@ use std::any::Any;
 
 use druid::kurbo::Line;
 use druid::widget::prelude::*;
-use druid::Point;
+use druid::{Point, Rect};
+
+use serde_json::{json, Value};
 
 use svg::node::element::Line as SvgLine;
 use svg::Document;
@@ -52,7 +55,7 @@ impl MLine {
 }
 
 impl Figure for MLine {
-    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, transform: &Transform) {
+    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, transform: &Transform, _env: &Env) {
         let start = transform.point(self.start);
         let finish = transform.point(self.finish);
         ctx.stroke(Line::new(start, finish), &self.common.color, self.width);
@@ -72,10 +75,28 @@ impl Figure for MLine {
         img.add(line)
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_points(self.start, self.finish))
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "line",
+            "s": point_to_json(self.start),
+            "f": point_to_json(self.finish),
+            "w": self.width,
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        let line = format!("line s={} f={} w={}", point_to_line(self.start), point_to_line(self.finish), self.width);
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
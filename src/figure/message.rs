@@ -1,19 +1,27 @@
 use crate::app_data::DrawProperties;
 use crate::figure::{CommonParams, Figure};
+use crate::overlay;
 use crate::svg_params::SvgParams;
+use crate::theme;
 use crate::transform::Transform;
 
 use std::any::Any;
 
 use druid::widget::prelude::*;
-use druid::{Color, Point};
+use druid::{Point, Rect};
 
 use druid::piet::{FontFamily, Text, TextLayout, TextLayoutBuilder};
 
+use serde_json::{json, Value};
+
+use svg::node::element::Text as SvgText;
+use svg::node::Text as SvgText2;
 use svg::Document;
 
 pub struct MMessage {
     message_ind: usize,
+    // name of the overlaid replay, it is shown but not a part of the message
+    prefix: String,
     text: String,
     common: CommonParams,
 }
@@ -22,21 +30,33 @@ impl MMessage {
     pub fn from_string(s: &str, draw_properties: &mut DrawProperties) -> Self {
         let message = MMessage {
             message_ind: draw_properties.was_messages,
+            prefix: match &draw_properties.layer {
+                Some(layer) => format!("{}: ", layer.name),
+                None => String::new(),
+            },
             text: String::from(&s[4..]),
-            common: CommonParams::default(),
+            // so that messages of overlaid replays can be hidden with their tags
+            common: CommonParams {
+                tags: draw_properties.layer.iter().map(|layer| layer.tag("")).collect(),
+                ..CommonParams::default()
+            },
         };
         draw_properties.was_messages += 1;
         message
     }
+
+    fn shown_text(&self) -> String {
+        format!("{}{}", self.prefix, self.text)
+    }
 }
 
 impl Figure for MMessage {
-    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, _transform: &Transform) {
+    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, _transform: &Transform, env: &Env) {
         let text = ctx.text();
         let layout = text
-            .new_text_layout(self.text.clone())
+            .new_text_layout(self.shown_text())
             .font(FontFamily::MONOSPACE, 10.0)
-            .text_color(Color::rgb8(255 as u8, 255 as u8, 255 as u8))
+            .text_color(env.get(theme::OVERLAY_TEXT_COLOR))
             // .alignment(TextAlignment::Start)
             .build()
             .unwrap();
@@ -46,14 +66,39 @@ impl Figure for MMessage {
         ctx.draw_text(&layout, Point::new(0.0, self.message_ind as f64 * text_size.height));
     }
 
-    fn draw_on_image(&self, img: Document, _params: &SvgParams) -> Document {
-        img
+    fn draw_on_image(&self, img: Document, params: &SvgParams) -> Document {
+        let font = params.overlay_font;
+        let text = SvgText::new()
+            .add(SvgText2::new(self.shown_text()))
+            .set("x", params.viewport.x0 + font * 0.5)
+            .set("y", params.viewport.y0 + font * 1.2 * (self.message_ind + 1) as f64)
+            .set("fill", overlay::color_to_string(&params.overlay_color))
+            .set("font-size", font)
+            .set("font-family", "monospace")
+            .set("xml:space", "preserve");
+        img.add(text)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        None
     }
 
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        json!({
+            "type": "msg",
+            "m": self.shown_text(),
+            "i": self.message_ind,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("msg {}", self.text)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
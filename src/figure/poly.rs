@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -9,7 +10,9 @@ use crate::transform::Transform;
 use std::any::Any;
 
 use druid::widget::prelude::*;
-use druid::Point;
+use druid::{Point, Rect};
+
+use serde_json::{json, Value};
 
 use svg::node::element::Polygon as SvgPolygon;
 use svg::node::element::Polyline as SvgPolyline;
@@ -53,7 +56,7 @@ impl MPoly {
 }
 
 impl Figure for MPoly {
-    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, transform: &Transform) {
+    fn draw(&self, ctx: &mut PaintCtx, _scale: f64, transform: &Transform, _env: &Env) {
         let points = self.points.iter().map(|&x| transform.point(x)).collect::<Vec<_>>();
         let poly = Poly::from_vec(&points);
         if self.fill {
@@ -88,10 +91,36 @@ impl Figure for MPoly {
         }
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        let first = *self.points.first()?;
+        Some(self.points.iter().fold(Rect::from_points(first, first), |rect, &p| rect.union_pt(p)))
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "poly",
+            "p": self.points.iter().map(|&p| point_to_json(p)).collect::<Vec<_>>(),
+            "f": self.fill,
+            "w": self.width,
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = "poly".to_string();
+        for &p in self.points.iter() {
+            line += &format!(" p={}", point_to_line(p));
+        }
+        line += &format!(" w={}", self.width);
+        if self.fill {
+            line += " f=1";
+        }
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{alignment_to_line, point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -10,6 +11,8 @@ use std::any::Any;
 use druid::widget::prelude::*;
 use druid::{Point, Rect};
 
+use serde_json::{json, Value};
+
 use svg::node::element::Rectangle as SvgRect;
 use svg::Document;
 
@@ -54,10 +57,8 @@ impl MRect {
             })
             .collect()
     }
-}
 
-impl Figure for MRect {
-    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform) {
+    fn aligned_center(&self) -> Point {
         let mut center = self.center;
         if self.alignment.0 == 'B' {
             center.x += self.size.x / 2.;
@@ -69,7 +70,13 @@ impl Figure for MRect {
         } else if self.alignment.1 == 'E' {
             center.y -= self.size.y / 2.;
         }
-        let center = transform.point(center);
+        center
+    }
+}
+
+impl Figure for MRect {
+    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform, _env: &Env) {
+        let center = transform.point(self.aligned_center());
         let mut size = self.size;
         size.x *= scale;
         size.y *= scale;
@@ -83,18 +90,7 @@ impl Figure for MRect {
 
     fn draw_on_image(&self, img: Document, params: &SvgParams) -> Document {
         let color = self.color_to_string();
-        let mut center = self.center;
-        if self.alignment.0 == 'B' {
-            center.x += self.size.x / 2.;
-        } else if self.alignment.0 == 'E' {
-            center.x -= self.size.x / 2.;
-        }
-        if self.alignment.1 == 'B' {
-            center.y += self.size.y / 2.;
-        } else if self.alignment.1 == 'E' {
-            center.y -= self.size.y / 2.;
-        }
-        let center = (params.transform)(center);
+        let center = (params.transform)(self.aligned_center());
         let mut rect = SvgRect::new()
             .set("x", center.x - self.size.x / 2.0)
             .set("y", params.size.height - (center.y + self.size.y / 2.0))
@@ -110,10 +106,34 @@ impl Figure for MRect {
         img.add(rect)
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(self.aligned_center(), Size::new(self.size.x, self.size.y)).abs())
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "rect",
+            "c": point_to_json(self.center),
+            "s": point_to_json(self.size),
+            "f": self.fill,
+            "w": self.width,
+            "a": format!("{}{}", self.alignment.0, self.alignment.1),
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!("rect c={} s={} w={}", point_to_line(self.center), point_to_line(self.size), self.width);
+        if self.fill {
+            line += " f=1";
+        }
+        line += &alignment_to_line(self.alignment);
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
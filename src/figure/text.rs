@@ -1,4 +1,5 @@
 use crate::app_data::DrawProperties;
+use crate::figure::common::{alignment_to_line, point_to_json, point_to_line};
 use crate::figure::{CommonParams, Figure};
 use crate::in_between::{interpolate, InBetweenProperties};
 use crate::parse::Params;
@@ -8,10 +9,12 @@ use crate::transform::Transform;
 use std::any::Any;
 
 use druid::widget::prelude::*;
-use druid::Point;
+use druid::{Point, Rect};
 
 use druid::piet::{FontFamily, Text, TextLayout, TextLayoutBuilder};
 
+use serde_json::{json, Value};
+
 use svg::node::element::Text as SvgText;
 use svg::node::Text as SvgText2;
 use svg::Document;
@@ -28,40 +31,17 @@ pub struct MText {
 }
 
 impl MText {
-    pub fn from_string(s: &str, draw_properties: &mut DrawProperties) -> Self {
-        let mut s = s.to_string();
-        let error_message = format!("Can't parse text from string [{}]", s);
-        let mut text = String::new();
-        for i in 0..s.len() - 2 {
-            if &s[i..i + 2] == "m=" {
-                let mut j: usize;
-                if s.chars().nth(i + 2).expect(&error_message) == '"' {
-                    j = i + 3;
-                    while s.chars().nth(j).expect(&error_message) != '"' {
-                        text.push(s.chars().nth(j).expect(&error_message));
-                        j += 1;
-                    }
-                    j += 1
-                } else {
-                    j = i + 2;
-                    while j < s.len() && s.chars().nth(j).expect(&error_message) != ' ' {
-                        text.push(s.chars().nth(j).expect(&error_message));
-                        j += 1;
-                    }
-                }
-                text = text.replace(";", "\n");
-                s = [s[..i - 1].to_string(), s[j..].to_string()].concat();
-                break;
-            }
-        }
+    /// `None` if the text has no closing quote.
+    pub fn from_string(s: &str, draw_properties: &mut DrawProperties) -> Option<Self> {
+        let (text, s) = split_text(s)?;
         let params = Params::from_str(&s);
-        Self {
+        Some(Self {
             center: params.get("c").unwrap_or(Point::new(0.0, 0.0)),
             text,
             font: params.get("s").unwrap_or(draw_properties.font),
             alignment: params.get("a").unwrap_or(('C', 'C')),
             common: CommonParams::new(&params, draw_properties),
-        }
+        })
     }
 
     pub fn in_betweens(a: &Self, b: &Self, in_between_properties: &InBetweenProperties) -> Vec<Self> {
@@ -88,8 +68,29 @@ impl MText {
     }
 }
 
+/// Text of `m=` and the rest of the line, `None` if the quotes of the text are not closed.
+pub fn split_text(s: &str) -> Option<(String, String)> {
+    let mut s = s.to_string();
+    let mut text = String::new();
+    if let Some(i) = s.find("m=") {
+        let rest = &s[i + 2..];
+        let len = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            text.push_str(&quoted[..end]);
+            end + 2
+        } else {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            text.push_str(&rest[..end]);
+            end
+        };
+        text = text.replace(';', "\n");
+        s.replace_range(i - 1..i + 2 + len, "");
+    }
+    Some((text, s))
+}
+
 impl Figure for MText {
-    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform) {
+    fn draw(&self, ctx: &mut PaintCtx, scale: f64, transform: &Transform, _env: &Env) {
         let font = self.font * scale;
 
         let text = ctx.text();
@@ -150,10 +151,39 @@ impl Figure for MText {
         img.add(text)
     }
 
+    fn bounds(&self) -> Option<Rect> {
+        // real size is only known after layout, so overestimate it to cover any alignment
+        let lines = self.text.lines().count().max(1) as f64;
+        let width = self.text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as f64 * self.font;
+        Some(Rect::from_center_size(self.center, Size::new(width * 2.0, lines * self.font * 2.4)))
+    }
+
     fn common(&self) -> &CommonParams {
         &self.common
     }
 
+    fn to_json(&self) -> Value {
+        self.common.with_json(json!({
+            "type": "text",
+            "c": point_to_json(self.center),
+            "m": self.text,
+            "s": self.font,
+            "a": format!("{}{}", self.alignment.0, self.alignment.1),
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        // new lines are written as `;`, as in the input
+        let mut line = format!(
+            "text m=\"{}\" c={} s={}",
+            self.text.replace('\n', ";"),
+            point_to_line(self.center),
+            self.font
+        );
+        line += &alignment_to_line(self.alignment);
+        self.common.with_line(line)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
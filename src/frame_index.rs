@@ -0,0 +1,326 @@
+use crate::app_data::DrawProperties;
+use crate::figure::Object;
+use crate::in_between::InBetweenProperties;
+use crate::loader::{in_between_frames, parse_line, Line, LoadUpdate, Setting};
+use crate::replay::{Frame, FrameRef, Header};
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+const SIDECAR_VERSION: u32 = 1;
+// in ticks, each of them may also hold in-betweens
+const CACHE_CAPACITY: usize = 32;
+
+/// Result of a single pass over the input: enough to parse any tick without reading the ones before it.
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    version: u32,
+    source_len: u64,
+    source_modified: Option<SystemTime>,
+    // byte ranges of `tick` lines
+    ticks: Vec<(u64, u64)>,
+    // settings and kept objects with their offsets, in the order of appearance
+    events: Vec<(u64, String)>,
+    tags: Vec<String>,
+    unparsed: usize,
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".rvidx");
+    PathBuf::from(s)
+}
+
+fn load_sidecar(path: &Path, metadata: &fs::Metadata) -> Option<Sidecar> {
+    let file = File::open(sidecar_path(path)).ok()?;
+    let sidecar: Sidecar = serde_json::from_reader(BufReader::new(file)).ok()?;
+    let valid = sidecar.version == SIDECAR_VERSION && sidecar.source_len == metadata.len() && sidecar.source_modified == metadata.modified().ok();
+    valid.then_some(sidecar)
+}
+
+fn save_sidecar(path: &Path, sidecar: &Sidecar) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(sidecar_path(path))?);
+    serde_json::to_writer(&mut writer, sidecar)?;
+    writer.flush()
+}
+
+fn scan(path: &Path, metadata: &fs::Metadata) -> io::Result<Sidecar> {
+    let mut reader = BufReader::with_capacity(1 << 20, File::open(path)?);
+    let mut sidecar = Sidecar {
+        version: SIDECAR_VERSION,
+        source_len: 0,
+        source_modified: metadata.modified().ok(),
+        ticks: Vec::new(),
+        events: Vec::new(),
+        tags: Vec::new(),
+        unparsed: 0,
+    };
+    let mut draw_properties = DrawProperties::new();
+    let mut tags_set = HashSet::new();
+    // everything before the first tick is kept, unless there are no ticks at all
+    let mut preamble = Vec::new();
+    let mut offset = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        let text = line.trim_end_matches(&['\n', '\r'][..]);
+        match parse_line(text, &mut draw_properties) {
+            Line::Tick => {
+                if sidecar.ticks.is_empty() {
+                    sidecar.events.append(&mut preamble);
+                    sidecar.events.sort_by_key(|&(offset, _)| offset);
+                }
+                sidecar.ticks.push((offset, offset + read));
+                eprint!("\rindexing tick {}", sidecar.ticks.len());
+                io::stderr().flush().unwrap();
+            }
+            Line::Setting(_) => sidecar.events.push((offset, text.to_string())),
+            Line::Object(x) => {
+                for tag in x.tags().iter() {
+                    if tags_set.insert(tag.clone()) {
+                        sidecar.tags.push(tag.clone());
+                    }
+                }
+                if sidecar.ticks.is_empty() {
+                    preamble.push((offset, text.to_string()));
+                } else if x.keep() {
+                    sidecar.events.push((offset, text.to_string()));
+                }
+            }
+            Line::Unparsed => sidecar.unparsed += 1,
+        }
+        offset += read;
+    }
+    eprintln!();
+    sidecar.source_len = offset;
+    Ok(sidecar)
+}
+
+/// Frames shown for one tick: in-betweens leading to it, then the tick itself.
+struct Segment {
+    start: u64,
+    end: u64,
+    first_frame: usize,
+    // state at the start of the tick
+    draw_properties: DrawProperties,
+    kept: usize,
+    // in effect at the end of the tick, as in-betweens are made then
+    in_between_properties: Arc<InBetweenProperties>,
+}
+
+/// Replays settings and kept objects to find the state at any offset.
+struct Replay<'a> {
+    events: Peekable<slice::Iter<'a, (u64, String)>>,
+    header: Header,
+    disabled: HashSet<String>,
+    draw_properties: DrawProperties,
+    in_between_properties: InBetweenProperties,
+    kept: Vec<Object>,
+}
+
+impl Replay<'_> {
+    fn until(&mut self, offset: u64) {
+        while let Some((_, line)) = self.events.next_if(|(event, _)| *event < offset) {
+            match parse_line(line, &mut self.draw_properties) {
+                Line::Setting(setting) => {
+                    if let Setting::Disable(tag) = &setting {
+                        self.disabled.insert(tag.clone());
+                    }
+                    setting.apply(&mut self.header, &mut self.in_between_properties);
+                }
+                Line::Object(x) => self.kept.push(x),
+                Line::Tick | Line::Unparsed => {}
+            }
+        }
+    }
+}
+
+/// Frames of a file that is too big to keep in memory. Only the offsets of ticks are stored,
+/// recently shown ticks are kept parsed.
+pub struct FrameIndex {
+    file: Mutex<File>,
+    segments: Vec<Segment>,
+    kept: Vec<Object>,
+    len: usize,
+    cache: Mutex<VecDeque<(usize, Arc<[Frame]>)>>,
+}
+
+impl FrameIndex {
+    fn build(file: File, sidecar: &Sidecar) -> LoadUpdate {
+        let ticks = &sidecar.ticks;
+        let bounds = if ticks.is_empty() {
+            vec![(0, sidecar.source_len)]
+        } else {
+            (0..ticks.len())
+                .map(|i| (ticks[i].1, ticks.get(i + 1).map_or(sidecar.source_len, |tick| tick.0)))
+                .collect()
+        };
+
+        let mut replay = Replay {
+            events: sidecar.events.iter().peekable(),
+            header: Header::default(),
+            disabled: HashSet::new(),
+            draw_properties: DrawProperties::new(),
+            in_between_properties: InBetweenProperties::new(),
+            kept: Vec::new(),
+        };
+        let mut in_between_properties = Arc::new(InBetweenProperties::new());
+        let mut segments = Vec::with_capacity(bounds.len());
+        let mut len = 0;
+        for (i, (start, end)) in bounds.into_iter().enumerate() {
+            replay.until(start);
+            let draw_properties = DrawProperties {
+                was_messages: 0,
+                ..replay.draw_properties.clone()
+            };
+            let kept = replay.kept.len();
+            replay.until(end);
+            if *in_between_properties != replay.in_between_properties {
+                in_between_properties = Arc::new(replay.in_between_properties.clone());
+            }
+
+            let first_frame = len;
+            len += 1;
+            if i > 0 && in_between_properties.frames != 1 {
+                len += in_between_properties.frames - 1;
+            }
+            segments.push(Segment {
+                start,
+                end,
+                first_frame,
+                draw_properties,
+                kept,
+                in_between_properties: in_between_properties.clone(),
+            });
+        }
+        replay.until(u64::MAX);
+
+        let tags = sidecar.tags.iter().map(|tag| (tag.clone(), !replay.disabled.contains(tag))).collect();
+        let index = FrameIndex {
+            file: Mutex::new(file),
+            segments,
+            kept: replay.kept,
+            len,
+            cache: Mutex::new(VecDeque::new()),
+        };
+        LoadUpdate {
+            header: replay.header,
+            tags,
+            index: Some(Arc::new(index)),
+            finished: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, ind: usize) -> Option<FrameRef> {
+        if ind >= self.len {
+            return None;
+        }
+        let segment = self.segments.partition_point(|segment| segment.first_frame <= ind) - 1;
+        match self.frames(segment) {
+            Ok(frames) => Some(FrameRef::new(frames, ind - self.segments[segment].first_frame)),
+            Err(e) => {
+                eprintln!("Can't read frame {}, {}", ind + 1, e);
+                None
+            }
+        }
+    }
+
+    pub fn first_frame_of_tick(&self, tick: usize) -> usize {
+        self.segments.get(tick).map_or(self.len, |segment| segment.first_frame)
+    }
+
+    fn frames(&self, segment: usize) -> io::Result<Arc<[Frame]>> {
+        if let Some(frames) = self.cached(segment) {
+            return Ok(frames);
+        }
+
+        let objects = self.parse(segment)?;
+        let properties = &self.segments[segment].in_between_properties;
+        let mut frames = Vec::new();
+        if segment > 0 && properties.frames != 1 {
+            let prev_frame = match self.cached(segment - 1) {
+                Some(prev) => prev.last().unwrap().objects.clone(),
+                None => self.parse(segment - 1)?,
+            };
+            frames.extend(
+                in_between_frames(&prev_frame, &objects, properties)
+                    .into_iter()
+                    .map(|objects| Frame::new(objects, segment)),
+            );
+        }
+        frames.push(Frame::new(objects, segment));
+        let frames: Arc<[Frame]> = frames.into();
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|(cached, _)| *cached != segment);
+        cache.push_front((segment, frames.clone()));
+        cache.truncate(CACHE_CAPACITY);
+        Ok(frames)
+    }
+
+    fn cached(&self, segment: usize) -> Option<Arc<[Frame]>> {
+        let mut cache = self.cache.lock().unwrap();
+        let pos = cache.iter().position(|(cached, _)| *cached == segment)?;
+        let entry = cache.remove(pos)?;
+        let frames = entry.1.clone();
+        cache.push_front(entry);
+        Some(frames)
+    }
+
+    // objects of the tick itself, without in-betweens
+    fn parse(&self, segment: usize) -> io::Result<Vec<Object>> {
+        let segment = &self.segments[segment];
+        let mut buf = vec![0; (segment.end - segment.start) as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(segment.start))?;
+            file.read_exact(&mut buf)?;
+        }
+
+        let mut draw_properties = segment.draw_properties.clone();
+        let mut objects = self.kept[..segment.kept].to_vec();
+        for line in String::from_utf8_lossy(&buf).lines() {
+            if let Line::Object(x) = parse_line(line, &mut draw_properties) {
+                objects.push(x);
+            }
+        }
+        Ok(objects)
+    }
+}
+
+/// Indexes the file instead of loading it, the index is saved next to the file and reused
+/// until the file changes.
+pub fn open(path: &Path, mut publish: impl FnMut(LoadUpdate)) -> io::Result<()> {
+    let metadata = fs::metadata(path)?;
+    let sidecar = match load_sidecar(path, &metadata) {
+        Some(sidecar) => sidecar,
+        None => {
+            let sidecar = scan(path, &metadata)?;
+            if let Err(e) = save_sidecar(path, &sidecar) {
+                eprintln!("Can't save index to {}, {}", sidecar_path(path).display(), e);
+            }
+            sidecar
+        }
+    };
+    eprintln!("unparsed: {}", sidecar.unparsed);
+
+    publish(FrameIndex::build(File::open(path)?, &sidecar));
+    Ok(())
+}
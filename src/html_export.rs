@@ -0,0 +1,85 @@
+use crate::export_options::ExportOptions;
+use crate::job::Job;
+use crate::replay::{Frame, Header};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use druid::Color;
+use serde_json::json;
+
+const PLAYER: &str = include_str!("player.html");
+const PLACEHOLDER: &str = "/*REPLAY*/null";
+
+/// Objects of the frame serialized for the player.
+pub fn frame_objects(frame: &Frame) -> Vec<String> {
+    frame.objects.iter().map(|item| item.to_json().to_string()).collect()
+}
+
+fn rgba(color: &Color) -> [u8; 4] {
+    let (r, g, b, a) = color.as_rgba8();
+    [r, g, b, a]
+}
+
+/// Writes frames into a single html file with a player. Objects repeated between frames are stored once.
+/// Nothing is written unless all `total_frames` frames come.
+pub fn save(
+    file_name: &str,
+    header: &Header,
+    tags: &[(String, bool)],
+    options: &ExportOptions,
+    frames: impl Iterator<Item = Result<Vec<String>, String>>,
+    total_frames: usize,
+    job: &Job,
+) -> io::Result<()> {
+    let mut objects = Vec::new();
+    let mut known = HashMap::new();
+    let mut frame_objects = Vec::with_capacity(total_frames);
+    for frame in frames {
+        if job.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let frame = frame
+            .map_err(io::Error::other)?
+            .into_iter()
+            .map(|object| {
+                *known.entry(object.clone()).or_insert_with(|| {
+                    objects.push(object);
+                    objects.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        frame_objects.push(frame);
+        job.done();
+    }
+    if frame_objects.len() < total_frames {
+        let error = format!("only {} of {} frames were made", frame_objects.len(), total_frames);
+        return Err(io::Error::other(error));
+    }
+
+    let replay = json!({
+        "size": [header.size.width, header.size.height],
+        "shift": [header.shift.width, header.shift.height],
+        "flipy": header.flipy,
+        "speed": header.fps_speed,
+        // the page around the canvas has colors of the theme, a transparent one has no background
+        "background": options.background(header).as_ref().map(rgba),
+        "canvas": Some(&options.theme.canvas).filter(|_| !options.transparent).map(rgba),
+        "text": rgba(&options.theme.text),
+        "tags": tags,
+        "frames": frame_objects,
+    })
+    .to_string();
+    // objects are already serialized, so they are added to the end of the object
+    let replay = format!("{},\"objects\":[{}]}}", &replay[..replay.len() - 1], objects.join(","));
+    // `</script>` inside of strings would end the script early
+    let replay = replay.replace("</", "<\\/");
+
+    let (before, after) = PLAYER.split_once(PLACEHOLDER).unwrap();
+    let mut writer = BufWriter::new(File::create(file_name)?);
+    writer.write_all(before.as_bytes())?;
+    writer.write_all(replay.as_bytes())?;
+    writer.write_all(after.as_bytes())?;
+    writer.flush()
+}
@@ -5,6 +5,7 @@ use crate::figure::CommonParams;
 use druid::Color;
 use druid::Point;
 
+#[derive(Clone, PartialEq)]
 pub struct InBetweenProperties {
     pub frames: usize,
     pub func: Vec<f64>,
@@ -117,7 +117,7 @@ impl Widget<AppData> for ISlider {
     fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _env: &Env) {}
 
     fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppData, data: &AppData, _env: &Env) {
-        self.max = data.frames.lock().unwrap().len().max(1) - 1;
+        self.max = data.frames.len().max(1) - 1;
         ctx.request_paint();
     }
 
@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use druid::{Data, ExtEventSink, Selector, Target};
+
+pub const JOB_UPDATE: Selector<JobStatus> = Selector::new("job_update");
+
+/// State of an export as shown in the window.
+#[derive(Clone, Data)]
+pub struct JobStatus {
+    pub title: String,
+    pub total: usize,
+    pub done: usize,
+    // reasons, one for each failed item
+    pub errors: Arc<Vec<String>>,
+    // files that already exist and are not replaced
+    pub skipped: Arc<Vec<String>>,
+    pub finished: bool,
+    cancel: Arc<AtomicBool>,
+}
+
+impl JobStatus {
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = if self.finished && self.is_cancelled() {
+            format!("{}: cancelled, {} of {} done", self.title, self.done, self.total)
+        } else if self.finished {
+            format!("{}: {} done", self.title, self.done)
+        } else {
+            format!("{}: {}/{}", self.title, self.done + self.errors.len() + self.skipped.len(), self.total)
+        };
+        if let Some(file) = self.skipped.first() {
+            text += &format!(", {} skipped ({} already exists)", self.skipped.len(), file);
+        }
+        if let Some(error) = self.errors.first() {
+            text += &format!(", {} failed ({})", self.errors.len(), error);
+        }
+        text
+    }
+}
+
+/// Export running in the background. Workers report every finished item, the window gets
+/// the new status each time, without a window progress is printed instead.
+#[derive(Clone)]
+pub struct Job {
+    status: Arc<Mutex<JobStatus>>,
+    sink: Option<ExtEventSink>,
+}
+
+impl Job {
+    pub fn new(title: impl Into<String>, total: usize, sink: Option<ExtEventSink>) -> Self {
+        let status = JobStatus {
+            title: title.into(),
+            total,
+            done: 0,
+            errors: Arc::new(Vec::new()),
+            skipped: Arc::new(Vec::new()),
+            finished: false,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        let job = Self {
+            status: Arc::new(Mutex::new(status)),
+            sink,
+        };
+        job.update(|_| {});
+        job
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.status.lock().unwrap().is_cancelled()
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn done(&self) {
+        self.update(|status| status.done += 1);
+    }
+
+    /// Items interrupted by cancelling are not failed, they are just not done.
+    pub fn fail(&self, reason: impl Into<String>) {
+        if !self.is_cancelled() {
+            let reason = reason.into();
+            self.update(|status| Arc::make_mut(&mut status.errors).push(reason));
+        }
+    }
+
+    /// The file is there already and is left as it is.
+    pub fn skip(&self, file: impl Into<String>) {
+        let file = file.into();
+        self.update(|status| Arc::make_mut(&mut status.skipped).push(file));
+    }
+
+    /// The final status, printed to the console if there is no window.
+    pub fn finish(&self) -> JobStatus {
+        self.update(|status| {
+            let missing = status.total.saturating_sub(status.done + status.errors.len() + status.skipped.len());
+            if missing > 0 && !status.is_cancelled() {
+                Arc::make_mut(&mut status.errors).push(format!("{} not processed", missing));
+            }
+            status.finished = true;
+        });
+        let status = self.status();
+        if self.sink.is_none() {
+            println!("\r{}", status.text());
+            for file in status.skipped.iter().skip(1) {
+                println!("Skipped {}, it already exists", file);
+            }
+            for error in status.errors.iter().skip(1) {
+                eprintln!("{}", error);
+            }
+        }
+        status
+    }
+
+    fn update(&self, f: impl FnOnce(&mut JobStatus)) {
+        // the lock is not held while the window is notified
+        let status = {
+            let mut status = self.status.lock().unwrap();
+            f(&mut status);
+            status.clone()
+        };
+        match &self.sink {
+            Some(sink) => {
+                // fails only if the window is already closed
+                let _ = sink.submit_command(JOB_UPDATE, status, Target::Auto);
+            }
+            None if !status.finished => {
+                print!("\r{}", status.text());
+                io::stdout().flush().unwrap();
+            }
+            None => {}
+        }
+    }
+}
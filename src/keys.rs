@@ -0,0 +1,58 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use druid::Code;
+
+/// What a key does in the viewer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Next,
+    Previous,
+    Play,
+    ResetRegion,
+    Fit,
+    Link,
+}
+
+// names in the settings, with default keys
+const ACTIONS: [(&str, Action, Code); 6] = [
+    ("next", Action::Next, Code::ArrowRight),
+    ("previous", Action::Previous, Code::ArrowLeft),
+    ("play", Action::Play, Code::Space),
+    ("reset_region", Action::ResetRegion, Code::Escape),
+    ("fit", Action::Fit, Code::Digit0),
+    ("link", Action::Link, Code::KeyL),
+];
+
+pub fn defaults() -> BTreeMap<String, String> {
+    ACTIONS.iter().map(|(name, _, code)| (name.to_string(), code.to_string())).collect()
+}
+
+pub struct KeyBindings(HashMap<Code, Action>);
+
+impl KeyBindings {
+    /// Key codes are named as in druid, e.g. `KeyA`, `Digit1`, `ArrowUp`.
+    pub fn new(keys: &BTreeMap<String, String>) -> Self {
+        let mut bindings = HashMap::new();
+        for (name, key) in keys {
+            let action = match ACTIONS.iter().find(|(action, _, _)| action == name) {
+                Some((_, action, _)) => *action,
+                None => {
+                    eprintln!("Unknown action {} in keys", name);
+                    continue;
+                }
+            };
+            match Code::from_str(key) {
+                Ok(code) => {
+                    bindings.insert(code, action);
+                }
+                Err(_) => eprintln!("Unknown key {} for {}", key, name),
+            }
+        }
+        Self(bindings)
+    }
+
+    pub fn action(&self, code: Code) -> Option<Action> {
+        self.0.get(&code).copied()
+    }
+}
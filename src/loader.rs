@@ -0,0 +1,645 @@
+use crate::app_data::DrawProperties;
+use crate::figure::{self, in_betweens, Object};
+use crate::frame_index::FrameIndex;
+use crate::in_between::InBetweenProperties;
+use crate::merge::{self, Layer};
+use crate::parallel::panic_message;
+use crate::parse::Params;
+use crate::replay::{Frame, Header};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use druid::{Color, Size};
+
+use threadpool::ThreadPool;
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+// in bytes, a chunk is extended to the next tick
+const PARALLEL_CHUNK_SIZE: usize = 1 << 22;
+// how often a followed file is checked for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+pub enum Setting {
+    Speed(f64),
+    Width(f64),
+    Font(f64),
+    Size(Size),
+    Shift(Size),
+    SvgWidth(f64),
+    FlipY,
+    Disable(String),
+    InBetweens(usize),
+    SetFunc(String, Vec<f64>),
+    Background(Color),
+}
+
+impl Setting {
+    /// Applies settings that describe the whole replay. Width and font are applied by `parse_line`,
+    /// disabled tags depend on the tags seen so far and are left to the caller.
+    pub fn apply(&self, header: &mut Header, in_between_properties: &mut InBetweenProperties) {
+        match self {
+            Setting::Speed(speed) => header.fps_speed = 1.0 / speed,
+            Setting::Size(size) => header.size = *size,
+            Setting::Shift(shift) => header.shift = *shift,
+            Setting::SvgWidth(scale) => header.svg_width_scale = *scale,
+            Setting::FlipY => header.flipy = true,
+            Setting::Background(color) => header.background = Some(color.clone()),
+            Setting::InBetweens(frames) => {
+                let frames = *frames;
+                in_between_properties.frames = frames;
+                in_between_properties.func = (1..frames).map(|x| x as f64 / frames as f64).collect();
+                in_between_properties.funcs.insert("line".to_string(), in_between_properties.func.clone());
+            }
+            Setting::SetFunc(name, values) => {
+                in_between_properties.funcs.insert(name.clone(), values.clone());
+            }
+            Setting::Width(_) | Setting::Font(_) | Setting::Disable(_) => {}
+        }
+    }
+
+    /// The line of the replay file that `parse_line` reads back as this setting.
+    pub fn to_line(&self) -> String {
+        match self {
+            Setting::Speed(speed) => format!("speed {}", speed),
+            Setting::Width(width) => format!("width {}", width),
+            Setting::Font(font) => format!("font {}", font),
+            Setting::Size(size) => format!("size ({},{})", size.width, size.height),
+            Setting::Shift(shift) => format!("shift ({},{})", shift.width, shift.height),
+            Setting::SvgWidth(scale) => format!("svgwidth {}", scale),
+            Setting::FlipY => "flipy".to_string(),
+            Setting::Disable(tag) => format!("disable {}", tag),
+            Setting::InBetweens(frames) => format!("in_betweens {}", frames),
+            Setting::SetFunc(name, values) => {
+                let values = values.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                format!("setfunc {} {}", name, values.join(" "))
+            }
+            Setting::Background(color) => {
+                let (r, g, b, a) = color.as_rgba8();
+                format!("background col=({},{},{},{})", r, g, b, a)
+            }
+        }
+    }
+}
+
+pub enum Line {
+    Tick,
+    Setting(Setting),
+    Object(Object),
+    Unparsed,
+}
+
+// both `size (10,20)` and `size=(10,20)` are accepted
+fn value(s: &str) -> &str {
+    let s = s.trim();
+    s.strip_prefix('=').unwrap_or(s)
+}
+
+fn parse_size(s: &str) -> Option<Size> {
+    let mut iter = value(s).strip_prefix('(')?.strip_suffix(')')?.split(',');
+    Some(Size::new(iter.next()?.parse().ok()?, iter.next()?.parse().ok()?))
+}
+
+// width and font only affect parsing of the following objects, so they are applied right here
+pub fn parse_line(line: &str, draw_properties: &mut DrawProperties) -> Line {
+    if line.starts_with("tick") {
+        draw_properties.was_messages = 0;
+        return Line::Tick;
+    }
+
+    let setting = if let Some(s) = line.strip_prefix("speed") {
+        value(s).parse().ok().map(Setting::Speed)
+    } else if let Some(s) = line.strip_prefix("width") {
+        value(s).parse().ok().map(Setting::Width)
+    } else if let Some(s) = line.strip_prefix("font") {
+        value(s).parse().ok().map(Setting::Font)
+    } else if let Some(s) = line.strip_prefix("size") {
+        parse_size(s).map(Setting::Size)
+    } else if let Some(s) = line.strip_prefix("shift") {
+        parse_size(s).map(Setting::Shift)
+    } else if let Some(s) = line.strip_prefix("svgwidth") {
+        value(s).parse().ok().map(Setting::SvgWidth)
+    } else if let Some(s) = line.strip_prefix("background") {
+        Params::from_str(s).get("col").map(Setting::Background)
+    } else if line.starts_with("flipy") {
+        Some(Setting::FlipY)
+    } else if let Some(s) = line.strip_prefix("disable ") {
+        Some(Setting::Disable(s.trim().to_string()))
+    } else if let Some(s) = line.strip_prefix("in_betweens") {
+        value(s).parse().ok().filter(|&frames| frames > 0).map(Setting::InBetweens)
+    } else if let Some(s) = line.strip_prefix("setfunc") {
+        let mut iter = s.split_whitespace();
+        match (iter.next(), iter.map(|x| x.parse().ok()).collect::<Option<Vec<f64>>>()) {
+            (Some(name), Some(values)) => Some(Setting::SetFunc(name.to_string(), values)),
+            _ => None,
+        }
+    } else {
+        return match figure::from_string(line, draw_properties) {
+            Some(x) => Line::Object(x),
+            None => Line::Unparsed,
+        };
+    };
+
+    match setting {
+        Some(mut setting) => {
+            match &mut setting {
+                Setting::Width(width) => draw_properties.width = *width,
+                Setting::Font(font) => draw_properties.font = *font,
+                Setting::Disable(name) | Setting::SetFunc(name, _) => {
+                    if let Some(layer) = &draw_properties.layer {
+                        *name = layer.tag(name);
+                    }
+                }
+                _ => {}
+            }
+            Line::Setting(setting)
+        }
+        None => Line::Unparsed,
+    }
+}
+
+/// Everything the loader produced since the previous update.
+#[derive(Default)]
+pub struct LoadUpdate {
+    pub header: Header,
+    pub frames: Vec<Frame>,
+    pub tags: Vec<(String, bool)>,
+    pub disabled: Vec<String>,
+    pub index: Option<Arc<FrameIndex>>,
+    // frames of the tick that is still being written, they replace the previous ones
+    pub provisional: Vec<Frame>,
+    pub finished: bool,
+}
+
+impl LoadUpdate {
+    /// Adds the next update to this one.
+    pub fn extend(&mut self, update: LoadUpdate) {
+        self.header = update.header;
+        self.frames.extend(update.frames);
+        self.tags.extend(update.tags);
+        self.disabled.extend(update.disabled);
+        if update.index.is_some() {
+            self.index = update.index;
+        }
+        self.provisional = update.provisional;
+        self.finished = update.finished;
+    }
+}
+
+/// Turns lines of the input into frames, taking care of kept objects and in-betweens.
+pub struct Loader {
+    header: Header,
+    draw_properties: DrawProperties,
+    in_between_properties: InBetweenProperties,
+    init_frame: Vec<Object>,
+    last_frame: Vec<Object>,
+    prev_frame: Option<Vec<Object>>,
+    is_initial_tick: bool,
+    tags_set: HashSet<String>,
+    disabled_tags: HashSet<String>,
+    update: LoadUpdate,
+    frames_count: usize,
+    ticks_count: usize,
+    unparsed: usize,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            header: Header::default(),
+            draw_properties: DrawProperties::new(),
+            in_between_properties: InBetweenProperties::new(),
+            init_frame: Vec::new(),
+            last_frame: Vec::new(),
+            prev_frame: None,
+            is_initial_tick: true,
+            tags_set: HashSet::new(),
+            disabled_tags: HashSet::new(),
+            update: LoadUpdate::default(),
+            frames_count: 0,
+            ticks_count: 0,
+            unparsed: 0,
+        }
+    }
+
+    pub fn frames_count(&self) -> usize {
+        self.frames_count
+    }
+
+    pub fn unparsed(&self) -> usize {
+        self.unparsed
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        let line = parse_line(line, &mut self.draw_properties);
+        self.push(line);
+    }
+
+    pub fn push(&mut self, line: Line) {
+        match line {
+            Line::Tick => {
+                if self.is_initial_tick {
+                    self.init_frame = self.last_frame.clone();
+                } else {
+                    self.add_frame();
+                }
+                self.last_frame = self.init_frame.clone();
+                self.is_initial_tick = false;
+            }
+            Line::Setting(setting) => self.apply_setting(setting),
+            Line::Object(x) => {
+                for tag in x.tags().iter() {
+                    // a group like `A/` is listed before its first tag, e.g. `A/units`
+                    if let Some((group, _)) = tag.split_once('/') {
+                        self.add_tag(format!("{}/", group));
+                    }
+                    self.add_tag(tag.clone());
+                }
+                if x.keep() {
+                    self.init_frame.push(x.clone());
+                }
+                self.last_frame.push(x);
+            }
+            Line::Unparsed => self.unparsed += 1,
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.add_frame();
+        self.update.finished = true;
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.update.frames.is_empty() || !self.update.tags.is_empty() || !self.update.disabled.is_empty()
+    }
+
+    pub fn take_update(&mut self) -> LoadUpdate {
+        let mut update = std::mem::take(&mut self.update);
+        update.header = self.header.clone();
+        update
+    }
+
+    fn add_tag(&mut self, tag: String) {
+        if !self.tags_set.contains(&tag) {
+            self.update.tags.push((tag.clone(), !self.disabled_tags.contains(&tag)));
+            self.tags_set.insert(tag);
+        }
+    }
+
+    fn apply_setting(&mut self, setting: Setting) {
+        if let Setting::Disable(tag) = &setting {
+            if self.tags_set.contains(tag) {
+                self.update.disabled.push(tag.clone());
+            }
+            self.disabled_tags.insert(tag.clone());
+        }
+        setting.apply(&mut self.header, &mut self.in_between_properties);
+    }
+
+    fn add_frame(&mut self) {
+        let frame = std::mem::take(&mut self.last_frame);
+        let frames = self.frames_of(&frame);
+        self.frames_count += frames.len();
+        self.update.frames.extend(frames);
+        self.ticks_count += 1;
+        self.prev_frame = Some(frame);
+    }
+
+    /// Frames that `finish` would add now, for a replay that is still being written.
+    pub fn pending_frames(&self) -> Vec<Frame> {
+        self.frames_of(&self.last_frame)
+    }
+
+    // the frame of the current tick with in-betweens leading to it
+    fn frames_of(&self, frame: &[Object]) -> Vec<Frame> {
+        let tick = self.ticks_count;
+        let mut frames = Vec::new();
+        let prev_frame = self.prev_frame.as_ref().filter(|_| self.in_between_properties.frames != 1);
+        if let Some(prev_frame) = prev_frame {
+            let in_between_frames = in_between_frames(prev_frame, frame, &self.in_between_properties);
+            frames.extend(in_between_frames.into_iter().map(|objects| Frame::new(objects, tick)));
+        }
+        frames.push(Frame::new(frame.to_vec(), tick));
+        frames
+    }
+}
+
+/// Frames to show between two consecutive ticks, `properties.frames - 1` of them.
+pub fn in_between_frames(prev_frame: &[Object], frame: &[Object], properties: &InBetweenProperties) -> Vec<Vec<Object>> {
+    let mut prev = HashMap::new();
+    let mut in_between_frames = vec![Vec::new(); properties.frames - 1];
+    for item in prev_frame.iter() {
+        if let Some(id) = item.common().id {
+            prev.insert(id, item);
+        } else {
+            for frame in in_between_frames.iter_mut() {
+                frame.push(item.clone());
+            }
+        }
+    }
+    for item in frame.iter() {
+        if let Some(id) = item.common().id {
+            if let Some(&prev_item) = prev.get(&id) {
+                let v = in_betweens(&**prev_item, &**item, properties);
+                if v.is_empty() {
+                    continue;
+                }
+                assert_eq!(v.len(), properties.frames - 1);
+                for (i, x) in v.into_iter().enumerate() {
+                    in_between_frames[i].push(x);
+                }
+            }
+        }
+    }
+    in_between_frames
+}
+
+/// Reads the whole input, publishing frames as soon as they are ready. Updates are batched,
+/// unless the next read may block (e.g. live input from a pipe).
+pub fn read<R: Read>(mut reader: BufReader<R>, mut publish: impl FnMut(LoadUpdate)) {
+    let mut loader = Loader::new();
+    let mut last_update = Instant::now();
+    let mut line = String::new();
+    loop {
+        if loader.has_pending() && (reader.buffer().is_empty() || last_update.elapsed() >= UPDATE_INTERVAL) {
+            publish(loader.take_update());
+            last_update = Instant::now();
+        }
+
+        line.clear();
+        if reader.read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let text = line.trim_end_matches(&['\n', '\r'][..]);
+        loader.push_line(text);
+        if text.starts_with("tick") {
+            eprint!("\rreading tick {}", loader.frames_count() + 1);
+            io::stderr().flush().unwrap();
+        }
+    }
+    loader.finish();
+
+    eprintln!();
+    eprintln!("unparsed: {}", loader.unparsed());
+
+    publish(loader.take_update());
+}
+
+/// Reads a file that is still being written, like `tail -f`. At its end everything read so far is
+/// published as finished and the file is checked again for new lines, a line is parsed only when it is
+/// complete. If the file is truncated or replaced, e.g. by the log of a new game, `restart` is called
+/// and the file is read from the start. Never returns unless the file can't be read.
+pub fn follow(path: &Path, mut publish: impl FnMut(LoadUpdate), mut restart: impl FnMut()) -> io::Result<()> {
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let mut file = File::open(path)?;
+        let opened = file.metadata()?;
+        let mut loader = Loader::new();
+        let mut last_update = Instant::now();
+        let mut line = Vec::new();
+        let mut pos = 0;
+        let mut caught_up = false;
+        loop {
+            let read = file.read(&mut buf)?;
+            if read > 0 {
+                pos += read as u64;
+                caught_up = false;
+                for part in buf[..read].split_inclusive(|&b| b == b'\n') {
+                    line.extend_from_slice(part);
+                    if line.ends_with(b"\n") {
+                        let text = String::from_utf8_lossy(&line);
+                        loader.push_line(text.trim_end_matches(&['\n', '\r'][..]));
+                        line.clear();
+                    }
+                }
+                if loader.has_pending() && last_update.elapsed() >= UPDATE_INTERVAL {
+                    let mut update = loader.take_update();
+                    update.provisional = loader.pending_frames();
+                    publish(update);
+                    last_update = Instant::now();
+                }
+                continue;
+            }
+
+            if !caught_up {
+                eprint!("\rreading tick {}", loader.frames_count() + 1);
+                io::stderr().flush().unwrap();
+                let mut update = loader.take_update();
+                // the last tick may be over, it is shown until more lines come
+                update.provisional = loader.pending_frames();
+                update.finished = true;
+                publish(update);
+                last_update = Instant::now();
+                caught_up = true;
+            }
+            thread::sleep(FOLLOW_INTERVAL);
+            // while a file is being replaced it may be missing for a moment
+            if let Ok(metadata) = fs::metadata(path) {
+                if metadata.len() < pos || !same_file(&opened, &metadata) {
+                    break;
+                }
+            }
+        }
+        eprintln!();
+        eprintln!("{} was replaced, reading it from the start", path.display());
+        restart();
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+// a replaced file is noticed only if it is shorter than the part already read
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
+
+/// Same as `read`, for several replays overlaid in one, see `merge`.
+pub fn read_merged<R: BufRead>(readers: Vec<R>, layers: Vec<Layer>, mut publish: impl FnMut(LoadUpdate)) -> io::Result<()> {
+    let mut loader = Loader::new();
+    let mut last_update = Instant::now();
+    merge::merge(readers, layers, |line| {
+        if let Line::Tick = line {
+            eprint!("\rreading tick {}", loader.frames_count() + 1);
+            io::stderr().flush().unwrap();
+        }
+        loader.push(line);
+        if loader.has_pending() && last_update.elapsed() >= UPDATE_INTERVAL {
+            publish(loader.take_update());
+            last_update = Instant::now();
+        }
+    })?;
+    loader.finish();
+
+    eprintln!();
+    eprintln!("unparsed: {}", loader.unparsed());
+
+    publish(loader.take_update());
+    Ok(())
+}
+
+/// Same as `read`, but chunks of ticks are parsed on a thread pool. Only merging parsed lines
+/// into frames is sequential, so it is meant for files rather than live input.
+/// Stops at the first chunk that fails to parse.
+pub fn read_parallel<R: Read + Send>(reader: BufReader<R>, threads: usize, publish: impl FnMut(LoadUpdate)) -> io::Result<()> {
+    read_chunks(reader, threads, PARALLEL_CHUNK_SIZE, publish)
+}
+
+// a chunk ends at the first tick after `chunk_size` bytes
+fn read_chunks<R: Read + Send>(mut reader: BufReader<R>, threads: usize, chunk_size: usize, mut publish: impl FnMut(LoadUpdate)) -> io::Result<()> {
+    let pool = ThreadPool::new(threads);
+    let (results_tx, results_rx) = mpsc::channel();
+    // limits the number of parsed chunks waiting to be merged
+    let (permits_tx, permits_rx) = mpsc::sync_channel(threads * 2);
+
+    thread::scope(|scope| {
+        let reading = scope.spawn(move || -> io::Result<()> {
+            // objects depend on width and font set before them, the rest is left to the merge
+            let mut draw_properties = DrawProperties::new();
+            let mut chunk_draw_properties = draw_properties.clone();
+            let mut chunk = String::new();
+            let mut chunks = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let eof = reader.read_line(&mut line)? == 0;
+                if eof || (chunk.len() >= chunk_size && line.starts_with("tick")) {
+                    // the merge is over if a chunk failed
+                    if permits_tx.send(()).is_err() {
+                        break;
+                    }
+                    let chunk = std::mem::take(&mut chunk);
+                    let mut chunk_draw_properties = std::mem::replace(&mut chunk_draw_properties, draw_properties.clone());
+                    let results_tx = results_tx.clone();
+                    pool.execute(move || {
+                        let lines = panic::catch_unwind(AssertUnwindSafe(|| {
+                            chunk.lines().map(|line| parse_line(line, &mut chunk_draw_properties)).collect::<Vec<_>>()
+                        }));
+                        // the receiver is gone if another chunk failed
+                        let _ = results_tx.send((chunks, lines.map_err(|e| panic_message(&*e))));
+                    });
+                    chunks += 1;
+                    if eof {
+                        break;
+                    }
+                }
+                if line.starts_with("width") || line.starts_with("font") {
+                    parse_line(line.trim_end_matches(&['\n', '\r'][..]), &mut draw_properties);
+                }
+                chunk.push_str(&line);
+            }
+            Ok(())
+        });
+
+        let mut loader = Loader::new();
+        let mut last_update = Instant::now();
+        let mut parsed = BTreeMap::new();
+        let mut merged = 0;
+        let mut failed = None;
+        for (ind, lines) in results_rx.iter() {
+            let lines = match lines {
+                Ok(lines) => lines,
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            };
+            parsed.insert(ind, lines);
+            while let Some(lines) = parsed.remove(&merged) {
+                for line in lines {
+                    loader.push(line);
+                }
+                permits_rx.recv().unwrap();
+                merged += 1;
+
+                eprint!("\rreading tick {}", loader.frames_count() + 1);
+                io::stderr().flush().unwrap();
+                if loader.has_pending() && last_update.elapsed() >= UPDATE_INTERVAL {
+                    publish(loader.take_update());
+                    last_update = Instant::now();
+                }
+            }
+        }
+        // lets the reading thread stop
+        drop(permits_rx);
+        reading.join().unwrap()?;
+        if let Some(e) = failed {
+            eprintln!();
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("can't parse the replay, {}", e)));
+        }
+        loader.finish();
+
+        eprintln!();
+        eprintln!("unparsed: {}", loader.unparsed());
+
+        publish(loader.take_update());
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tags appear in different chunks, `disable`, `setfunc`, `width` and kept objects are used by later chunks
+    const REPLAY: &str = "size (100,100)
+in_betweens 3
+setfunc f 0.2 0.9
+width 3
+rect c=(1,1) s=(1,1) t=ground k=1
+tick
+msg first
+rect c=(10,10) s=(2,2) id=1 t=unit fu=f
+circle c=(5,5) r=1 id=2 t=unit
+tick
+disable unit
+rect c=(20,20) s=(2,2) id=1 t=unit fu=f
+circle c=(6,6) r=2 id=2 t=shot
+line s=(1,1) f=(3,3) t=shot k=1
+tick
+font 20
+msg second
+text c=(1,1) \"label\" t=label
+rect c=(30,30) s=(2,2) id=1 t=unit fu=f
+tick
+setfunc f 0.5 0.5
+rect c=(40,40) s=(2,2) id=1 t=unit fu=f
+poly p=(1,1) p=(2,2) p=(1,2) t=area
+tick
+";
+
+    fn lines(frame: &Frame) -> (usize, Vec<String>) {
+        (frame.tick, frame.objects.iter().map(|object| object.to_line()).collect())
+    }
+
+    #[test]
+    fn parallel_read_is_the_same_as_sequential() {
+        let mut sequential = LoadUpdate::default();
+        read(BufReader::new(REPLAY.as_bytes()), |update| sequential.extend(update));
+        assert_eq!(sequential.frames.iter().filter(|frame| frame.tick == 4).count(), 3);
+        let tags = sequential.tags.iter().map(|(tag, _)| tag.as_str()).collect::<Vec<_>>();
+        assert_eq!(tags, ["ground", "unit", "shot", "label", "area"]);
+        assert_eq!(sequential.disabled, ["unit"]);
+
+        for chunk_size in [1, 50, 120, PARALLEL_CHUNK_SIZE] {
+            let mut parallel = LoadUpdate::default();
+            read_chunks(BufReader::new(REPLAY.as_bytes()), 3, chunk_size, |update| parallel.extend(update)).unwrap();
+            assert_eq!(parallel.frames.len(), sequential.frames.len(), "chunk size {}", chunk_size);
+            assert_eq!(parallel.tags, sequential.tags, "chunk size {}", chunk_size);
+            assert_eq!(parallel.disabled, sequential.disabled, "chunk size {}", chunk_size);
+            for (a, b) in parallel.frames.iter().zip(sequential.frames.iter()) {
+                assert_eq!(lines(a), lines(b), "chunk size {}", chunk_size);
+            }
+        }
+    }
+}
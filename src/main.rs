@@ -1,53 +1,89 @@
 use std::env;
-use std::sync::{Arc, Mutex};
+use std::process;
+use std::sync::Arc;
 use std::thread;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use std::fs;
 use std::fs::File;
-use std::io::{self, BufRead, Write};
-
-use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
 
+use druid::piet::{FontFamily, StrokeStyle, Text, TextLayout, TextLayoutBuilder};
 use druid::widget::prelude::*;
-use druid::widget::{Align, CrossAxisAlignment, Flex, Label, MainAxisAlignment, SizedBox, Widget};
-use druid::Code;
+use druid::widget::{Align, Button, CrossAxisAlignment, Either, Flex, Label, MainAxisAlignment, SizedBox, Widget};
 use druid::WindowId;
-use druid::{AppLauncher, MouseButton, Point, Size, TimerToken, WidgetExt, WindowDesc};
-use druid::{Command, Selector, Target};
+use druid::{lens, Command, Selector, SingleUse, Target};
+use druid::{AppLauncher, MouseButton, Point, Rect, Size, TimerToken, WidgetExt, WindowDesc};
 use druid::{Menu, MenuItem};
 
-use svg::node::element::Rectangle as SvgRect;
-use svg::Document;
 mod svg_params;
-use svg_params::SvgParams;
-
-use threadpool::ThreadPool;
-
-use subprocess::{Popen, PopenConfig, Redirection};
 
 mod settings;
-use settings::Settings;
+use settings::get_settings;
 
+mod animated_svg;
+mod animation;
 mod app_data;
 mod checklist;
+mod cli;
+mod compare;
+mod contact_sheet;
+mod delegate;
+mod diff;
+mod export;
+mod export_config;
+mod export_dialog;
+mod export_options;
+mod external;
 mod figure;
+mod frame_index;
+mod html_export;
 mod in_between;
 mod islider;
+mod job;
+mod keys;
+mod loader;
+mod merge;
+mod overlay;
+mod parallel;
 mod parse;
 mod poly;
+mod raster;
+mod replay;
+mod slice;
+mod spatial_index;
+mod stats;
+mod theme;
 mod transform;
+mod validate;
+mod watch;
 
+use animation::Format;
 use checklist::Checklist;
-use figure::in_betweens;
-use in_between::InBetweenProperties;
+use compare::UnmatchedCache;
+use delegate::{Delegate, LOAD_UPDATE, RELOAD};
 
 use app_data::*;
+use export_options::ExportOptions;
 use islider::ISlider;
+use job::JobStatus;
+use keys::{Action, KeyBindings};
+use loader::LoadUpdate;
+use merge::Layer;
+use theme::Theme;
 use transform::Transform;
 
 const PADDING: f64 = 8.0;
+// in pixels, line widths don't scale with zoom
+const CULLING_MARGIN: f64 = 32.0;
+// bigger files are indexed instead of being loaded into memory
+const LAZY_LOADING_THRESHOLD: u64 = 512 << 20;
+// in pixels, around objects that only one of compared panes has
+const DIFF_MARGIN: f64 = 3.0;
+// how long "reloaded" is shown after the replay is read again
+const RELOADED_SHOWN_FOR: Duration = Duration::from_millis(1500);
 
 struct DrawingWidget {
     scale: f64,
@@ -58,271 +94,98 @@ struct DrawingWidget {
     timer_id: TimerToken,
     running: bool,
     last_data_size: Size,
+    // in image coordinates, which match the ones of `transform`
+    export_region: Option<Rect>,
+    // corners of the region being selected, in pixels
+    selection: Option<(Point, Point)>,
+    // number of the pane when panes are compared side by side
+    pane: Option<usize>,
+    // the shared camera as it was last seen
+    last_camera: Option<(Point, f64)>,
+    keys: KeyBindings,
+    // valid while "reloaded" is shown
+    reloaded_timer: TimerToken,
+    unmatched: UnmatchedCache,
 }
 
 impl DrawingWidget {
-    fn transform(&self, mut p: Point) -> Point {
-        p.x = (p.x - self.center.x) * self.scale + self.size.width / 2.0;
-        p.y = (p.y - self.center.y) * self.scale + self.size.height / 2.0;
-        p
-    }
-
-    fn inv_transform(&self, mut p: Point) -> Point {
-        p.x = (p.x - self.size.width / 2.0) / self.scale + self.center.x;
-        p.y = (p.y - self.size.height / 2.0) / self.scale + self.center.y;
-        p
-    }
-
-    fn internal_save_frame_as_svg(&self, data: &AppData, frame: usize, file_name: &str) {
-        let size = data.size.lock().unwrap().clone();
-
-        let mut img = Document::new()
-            .set("viewBox", (0, 0, size.width, size.height))
-            .set("width", size.width)
-            .set("height", size.height);
-
-        let enabled_tags = data
-            .tags
-            .lock()
-            .unwrap()
-            .iter()
-            .filter(|(_, b)| *b)
-            .map(|(tag, _)| tag.clone())
-            .collect::<HashSet<String>>();
-
-        let frame = &data.frames.lock().unwrap()[frame];
-
-        let rect = SvgRect::new()
-            .set("x", 0)
-            .set("y", 0)
-            .set("width", size.width)
-            .set("height", size.height)
-            .set("fill", "rgb(41, 41, 41)");
-        img = img.add(rect);
-
-        let flipy = *data.flipy.lock().unwrap();
-        let shift = data.shift.lock().unwrap().clone();
-        let transform = |mut p: Point| -> Point {
-            p.x += shift.width;
-            p.y += shift.height;
-            if flipy {
-                p.y = size.height - p.y;
-            }
-            p
-        };
-
-        let params = SvgParams {
-            size: size.clone(),
-            width_scale: *data.svg_width_scale.lock().unwrap(),
-            flipy,
-            transform: &transform,
-        };
-
-        for ind in frame.iter() {
-            let item = &data.objects.lock().unwrap()[*ind];
-            if item.need_to_draw(&enabled_tags) {
-                img = item.draw_on_image(img, &params);
-            }
+    fn new(pane: Option<usize>) -> Self {
+        Self {
+            scale: 1.0,
+            center: Point::new(0.0, 0.0),
+            size: Size::new(1.0, 1.0),
+            last_mouse_pos: Point::new(0.0, 0.0),
+            mouse_down: false,
+            timer_id: TimerToken::INVALID,
+            running: false,
+            last_data_size: Size::new(0.0, 0.0),
+            export_region: None,
+            selection: None,
+            pane,
+            last_camera: None,
+            keys: KeyBindings::new(get_settings().keys.as_ref().unwrap()),
+            reloaded_timer: TimerToken::INVALID,
+            unmatched: UnmatchedCache::default(),
         }
-
-        svg::save(file_name, &img).unwrap();
     }
 
-    fn internal_save_frame_as_png(&self, data: &AppData, frame: usize, file_name: &str) -> std::thread::JoinHandle<()> {
-        let svg_file_name = format!("_tmp_frame_{}_.svg", frame);
-        self.internal_save_frame_as_svg(data, frame, &svg_file_name);
-        let settings = get_settings();
-
-        let png_file = Arc::new(Mutex::new(file_name.to_string().clone()));
-        let svg_file = Arc::new(Mutex::new(svg_file_name.to_string().clone()));
-
-        if settings.conversion_tool.as_ref().unwrap() == "rsvg-convert" {
-            thread::spawn(move || {
-                std::process::Command::new("rsvg-convert".to_string())
-                    .arg(&*svg_file.lock().unwrap())
-                    .arg("-o")
-                    .arg(&*png_file.lock().unwrap())
-                    .arg("-w")
-                    .arg(settings.frame_resolution.unwrap().to_string())
-                    .output()
-                    .unwrap();
-                fs::remove_file(&*svg_file.lock().unwrap()).unwrap()
-            })
-        } else {
-            thread::spawn(move || {
-                std::process::Command::new(settings.inkscape_path.unwrap())
-                    .arg("-o")
-                    .arg(&*png_file.lock().unwrap())
-                    .arg("-w")
-                    .arg(settings.frame_resolution.unwrap().to_string())
-                    .arg(&*svg_file.lock().unwrap())
-                    .output()
-                    .unwrap();
-                fs::remove_file(&*svg_file.lock().unwrap()).unwrap()
-            })
+    fn tags<'a>(&self, data: &'a AppData) -> &'a [(String, bool)] {
+        match self.pane {
+            Some(pane) => &data.panes[pane].tags,
+            None => &data.tags,
         }
     }
 
-    fn save_frame_as_svg(&self, data: &AppData) {
-        if data.frame >= data.frames.lock().unwrap().len() {
-            return;
-        }
-
-        let frame = data.frame;
-        self.internal_save_frame_as_svg(data, frame, "frame.svg");
-        println!("Saved frame {} as frame.svg", frame + 1);
+    fn fit(&mut self, data_size: Size) {
+        self.center = Point::new(data_size.width / 2.0, data_size.height / 2.0);
+        self.scale = (self.size.height / data_size.height).min(self.size.width / data_size.width) * 0.9;
+        self.last_data_size = data_size;
     }
 
-    fn save_all_frames_as_svg(&self, data: &AppData) {
-        match fs::remove_dir_all("frames") {
-            Err(e) => {
-                eprintln!("Can't remove folder frames, {}", e);
-            }
-            _ => {}
-        };
-        fs::create_dir_all("frames").unwrap();
-
-        let total_frames = data.frames.lock().unwrap().len();
-        for frame in 0..total_frames {
-            print!("\rSaving frame {}/{}", frame + 1, total_frames);
-            io::stdout().flush().unwrap();
-            self.internal_save_frame_as_svg(data, frame, &format!("frames/{:05}.svg", frame + 1));
+    // other panes follow while cameras are linked
+    fn share_camera(&mut self, data: &mut AppData) {
+        if data.linked {
+            data.camera = Some((self.center, self.scale));
+            self.last_camera = data.camera;
         }
-        println!("\r{} frames saved in folder frames", total_frames);
     }
 
-    fn save_frame_as_png(&self, data: &AppData) {
-        if data.frame >= data.frames.lock().unwrap().len() {
-            return;
-        }
+    fn transform(&self, mut p: Point) -> Point {
+        p.x = (p.x - self.center.x) * self.scale + self.size.width / 2.0;
+        p.y = (p.y - self.center.y) * self.scale + self.size.height / 2.0;
+        p
+    }
 
-        let frame = data.frame;
-        let handle = self.internal_save_frame_as_png(data, frame, "frame.png");
-        thread::spawn(move || {
-            handle.join().unwrap();
-            println!("Saved frame {} as frame.png", frame + 1);
-        });
+    fn inv_transform(&self, mut p: Point) -> Point {
+        p.x = (p.x - self.size.width / 2.0) / self.scale + self.center.x;
+        p.y = (p.y - self.size.height / 2.0) / self.scale + self.center.y;
+        p
     }
 
-    fn save_all_frames_as_png(&self, data: &AppData) {
-        match fs::remove_dir_all("frames") {
-            Err(e) => {
-                eprintln!("Can't remove folder frames, {}", e);
+    // visible part of the widget in input coordinates, i.e. inverse of Transform::point
+    fn visible_rect(&self, shift: Size, data_size: Size, flipy: bool) -> Rect {
+        let to_input = |p: Point| -> Point {
+            let mut p = self.inv_transform(p);
+            if !flipy {
+                p.y = data_size.height - p.y;
             }
-            _ => {}
+            Point::new(p.x - shift.width, p.y - shift.height)
         };
-        fs::create_dir_all("frames").unwrap();
-
-        let total_frames = data.frames.lock().unwrap().len();
-        let settings = get_settings();
-        let pool = ThreadPool::new(settings.max_threads.unwrap());
-        for frame in 0..total_frames {
-            let svg_file = format!("frames/_tmp_frame_{}_.svg", frame + 1);
-            self.internal_save_frame_as_svg(data, frame, &svg_file);
-
-            print!("\rCreated svg {}/{}", frame + 1, total_frames);
-            io::stdout().flush().unwrap();
-        }
-
-        for frame in 0..total_frames {
-            let svg_file = format!("frames/_tmp_frame_{}_.svg", frame + 1);
-            let png_file = format!("frames/{:05}.png", frame + 1);
-            let inkscape_path = settings.inkscape_path.clone().unwrap();
-            let frame_resolution = settings.frame_resolution.unwrap();
-
-            if settings.conversion_tool.as_ref().unwrap() == "rsvg-convert" {
-                pool.execute(move || {
-                    let result = std::process::Command::new("rsvg-convert".to_string())
-                        .arg(&svg_file)
-                        .arg("-o")
-                        .arg(&png_file)
-                        .arg("-w")
-                        .arg(frame_resolution.to_string())
-                        .output();
-                    match result {
-                        Err(e) => {
-                            eprintln!("Can't run rsvg-convert, {}", e);
-                            return;
-                        }
-                        _ => {}
-                    };
-                    fs::remove_file(&svg_file).unwrap();
-                    print!("\rSaved frame {}/{}", frame + 1, total_frames);
-                    io::stdout().flush().unwrap();
-                });
-            } else {
-                pool.execute(move || {
-                    let mut timeout = 30;
-                    loop {
-                        let mut p = Popen::create(
-                            &[
-                                inkscape_path.clone(),
-                                "-o".to_string(),
-                                png_file.clone(),
-                                "-w".to_string(),
-                                frame_resolution.to_string(),
-                                svg_file.clone(),
-                            ],
-                            PopenConfig::default(),
-                        )
-                        .unwrap();
-
-                        p.wait_timeout(Duration::from_secs(timeout)).unwrap();
-                        if let None = p.poll() {
-                            p.terminate().unwrap();
-                            eprintln!("\rFrame {} failed       ", &frame);
-                            if timeout == 90 {
-                                eprintln!("\rMax attempts for frame {} reached", &frame);
-                                break;
-                            }
-                            timeout += 30;
-                            continue;
-                        }
-                        break;
-                    }
-                    fs::remove_file(&svg_file).unwrap();
-                    print!("\rSaved frame {}/{}", frame + 1, total_frames);
-                    io::stdout().flush().unwrap();
-                });
-            }
-        }
-        print!("\r                               ");
+        let margin = CULLING_MARGIN / self.scale;
+        Rect::from_points(to_input(Point::ZERO), to_input(Point::new(self.size.width, self.size.height))).inflate(margin, margin)
+    }
 
-        thread::spawn(move || {
-            pool.join();
-            println!("\rSaved {} frames in folder frames", total_frames);
-        });
+    fn export_options(&self) -> ExportOptions {
+        ExportOptions::new(&get_settings(), self.export_region)
     }
 
-    fn make_video_from_frames(&self, data: &AppData) {
-        let fps = *data.fps_speed.lock().unwrap();
-        thread::spawn(move || {
-            match fs::remove_file("video.mp4".to_string()) {
-                _ => {}
-            };
-            let result = Popen::create(
-                &format!(
-                    "ffmpeg -r {} -i frames/%05d.png -c:v libx264 -vf pad=ceil(iw/2)*2:ceil(ih/2)*2 -pix_fmt yuv420p video.mp4",
-                    1. / fps
-                )
-                .split_whitespace()
-                .collect::<Vec<_>>(),
-                PopenConfig {
-                    stdin: Redirection::Pipe,
-                    stdout: Redirection::Pipe,
-                    ..Default::default()
-                },
-            );
-            match result {
-                Ok(mut p) => {
-                    p.wait().unwrap();
-                    println!("Video created");
-                }
-                Err(e) => {
-                    eprintln!("Can't run ffmpeg, {}", e);
-                }
-            };
-        });
+    // exported with the tags shown in the pane, in compare mode those of `data` are all enabled
+    fn export_data(&self, data: &AppData) -> AppData {
+        let mut data = data.clone();
+        if let Some(pane) = self.pane {
+            data.tags = data.panes[pane].tags.clone();
+        }
+        data
     }
 }
 
@@ -331,10 +194,14 @@ impl Widget<AppData> for DrawingWidget {
         ctx.request_focus();
         match event {
             Event::MouseMove(e) => {
-                if e.buttons.contains(MouseButton::Left) && self.mouse_down {
+                if let Some((_, end)) = &mut self.selection {
+                    *end = e.pos;
+                    ctx.request_paint();
+                } else if e.buttons.contains(MouseButton::Left) && self.mouse_down {
                     self.center.x -= (e.pos.x - self.last_mouse_pos.x) / self.scale;
                     self.center.y -= (e.pos.y - self.last_mouse_pos.y) / self.scale;
                     self.last_mouse_pos = e.pos;
+                    self.share_camera(data);
                     ctx.request_paint();
                 }
             }
@@ -347,73 +214,134 @@ impl Widget<AppData> for DrawingWidget {
 
                 self.center.x -= mouse_now.x - mouse_was.x;
                 self.center.y -= mouse_now.y - mouse_was.y;
+                self.share_camera(data);
 
                 ctx.request_paint();
             }
             Event::MouseDown(e) => {
-                self.mouse_down = true;
-                self.last_mouse_pos = e.pos.clone();
+                if e.mods.shift() {
+                    self.selection = Some((e.pos, e.pos));
+                } else {
+                    self.mouse_down = true;
+                    self.last_mouse_pos = e.pos.clone();
+                }
             }
             Event::MouseUp(_) => {
                 self.mouse_down = false;
+                if let Some((start, end)) = self.selection.take() {
+                    let region = Rect::from_points(self.inv_transform(start), self.inv_transform(end));
+                    if region.area() > 0.0 {
+                        self.export_region = Some(region);
+                    }
+                    ctx.request_paint();
+                }
             }
-            Event::KeyDown(e) => match e.code {
-                Code::ArrowRight => {
-                    if data.frame + 1 < data.frames.lock().unwrap().len() {
+            Event::KeyDown(e) => match self.keys.action(e.code) {
+                Some(Action::Next) => {
+                    if data.frame + 1 < data.frames.len() {
                         data.frame += 1;
                         ctx.request_paint();
                     }
                 }
-                Code::ArrowLeft => {
+                Some(Action::Previous) => {
                     if data.frame != 0 {
                         data.frame -= 1;
                         ctx.request_paint();
                     }
                 }
-                Code::Space => {
+                Some(Action::Play) => {
                     if self.running {
                         self.running = false;
                         self.timer_id = TimerToken::INVALID;
                     } else {
-                        if data.frame + 1 == data.frames.lock().unwrap().len() {
+                        if data.frame + 1 == data.frames.len() {
                             data.frame = 0;
                             ctx.request_paint();
                         }
                         self.running = true;
-                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(*data.fps_speed.lock().unwrap()));
+                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(data.header.fps_speed));
                     }
                 }
-                Code::Digit0 => {
-                    self.last_data_size = Size::new(0.0, 0.0);
+                Some(Action::ResetRegion) => {
+                    self.export_region = None;
                     ctx.request_paint();
                 }
+                Some(Action::Fit) => {
+                    self.fit(data.header.size);
+                    self.share_camera(data);
+                    ctx.request_paint();
+                }
+                Some(Action::Link) => {
+                    data.linked = !data.linked;
+                    self.share_camera(data);
+                }
                 _ => (),
             },
+            Event::Timer(id) if *id == self.reloaded_timer => {
+                self.reloaded_timer = TimerToken::INVALID;
+                ctx.request_paint();
+            }
             Event::Timer(id) => {
                 if *id == self.timer_id {
-                    if data.frame + 1 < data.frames.lock().unwrap().len() {
+                    if data.frame + 1 < data.frames.len() {
                         data.frame += 1;
-                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(*data.fps_speed.lock().unwrap()));
+                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(data.header.fps_speed));
                         ctx.request_paint();
-                    } else if !*data.finished.lock().unwrap() {
-                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(*data.fps_speed.lock().unwrap()));
+                    } else if !data.finished || data.following {
+                        self.timer_id = ctx.request_timer(Duration::from_secs_f64(data.header.fps_speed));
                     } else {
                         self.running = false;
                         self.timer_id = TimerToken::INVALID;
                     }
                 }
             }
+            // exports are started by the first pane only
+            Event::Command(_) if self.pane.is_some_and(|pane| pane > 0) => ctx.request_paint(),
             Event::Command(c) => {
-                if c.is::<()>(Selector::new("save_frame_as_svg")) {
-                    self.save_frame_as_svg(data);
+                let sink = Some(ctx.get_external_handle());
+                let export_data = &self.export_data(data);
+                let export = if c.is::<()>(Selector::new("save_frame_as_svg")) {
+                    export::save_frame_as_svg(export_data, self.export_options(), sink)
                 } else if c.is::<()>(Selector::new("save_frame_as_png")) {
-                    self.save_frame_as_png(data);
+                    export::save_frame_as_png(export_data, self.export_options(), sink)
                 } else if c.is::<()>(Selector::new("save_all_frames_as_svg")) {
-                    self.save_all_frames_as_svg(data);
+                    export::save_all_frames_as_svg(export_data, self.export_options(), sink)
                 } else if c.is::<()>(Selector::new("save_all_frames_as_png")) {
-                    self.save_all_frames_as_png(data);
+                    export::save_all_frames_as_png(export_data, self.export_options(), sink)
+                } else if c.is::<()>(Selector::new("export_gif")) {
+                    export::export_animation(export_data, self.export_options(), Format::Gif, sink)
+                } else if c.is::<()>(Selector::new("export_apng")) {
+                    export::export_animation(export_data, self.export_options(), Format::Apng, sink)
+                } else if c.is::<()>(Selector::new("export_animated_svg")) {
+                    export::export_animated_svg(export_data, self.export_options(), sink)
+                } else if c.is::<()>(Selector::new("export_visible_area")) {
+                    let size = self.size;
+                    self.export_region = Some(Rect::from_points(
+                        self.inv_transform(Point::ZERO),
+                        self.inv_transform(Point::new(size.width, size.height)),
+                    ));
+                    None
+                } else if c.is::<()>(Selector::new("reset_export_region")) {
+                    self.export_region = None;
+                    None
+                } else if c.is::<()>(Selector::new("export_sheet_svg")) {
+                    export::export_contact_sheet(export_data, self.export_options(), "svg", sink)
+                } else if c.is::<()>(Selector::new("export_sheet_png")) {
+                    export::export_contact_sheet(export_data, self.export_options(), "png", sink)
+                } else if c.is::<()>(Selector::new("export_html")) {
+                    export::export_html(export_data, self.export_options(), sink)
                 } else if c.is::<()>(Selector::new("make_video_from_frames")) {
-                    self.make_video_from_frames(data);
+                    export::make_video_from_frames(export_data, sink)
+                } else if c.is::<()>(Selector::new("open_export_settings")) {
+                    let dialog = export_dialog::make_export_dialog().lens(AppData::export);
+                    ctx.new_window(WindowDesc::new(dialog).window_size((480.0, 300.0)).title("Export settings"));
+                    None
+                } else {
+                    None
+                };
+                // shown right away, so that another export can't start before the job reports
+                if let Some((status, _)) = export {
+                    data.job = Some(status);
                 }
                 ctx.request_paint();
             }
@@ -423,7 +351,10 @@ impl Widget<AppData> for DrawingWidget {
 
     fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _env: &Env) {}
 
-    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppData, _data: &AppData, _env: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        if data.reloads != old_data.reloads {
+            self.reloaded_timer = ctx.request_timer(RELOADED_SHOWN_FOR);
+        }
         ctx.request_paint();
     }
 
@@ -431,39 +362,81 @@ impl Widget<AppData> for DrawingWidget {
         bc.max()
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, env: &Env) {
         self.size = ctx.size();
 
-        let data_size = data.size.lock().unwrap().clone();
+        let background = data.header.background.clone().unwrap_or_else(|| env.get(theme::CANVAS_BACKGROUND));
+        ctx.fill(self.size.to_rect(), &background);
+
+        let data_size = data.header.size;
 
         if data_size != self.last_data_size {
-            self.center = Point::new(data_size.width / 2.0, data_size.height / 2.0);
-            self.scale = (self.size.height / data_size.height).min(self.size.width / data_size.width) * 0.9;
-            self.last_data_size = data_size;
+            self.fit(data_size);
+        }
+        if let Some((center, scale)) = data.camera.filter(|_| data.linked && data.camera != self.last_camera) {
+            self.center = center;
+            self.scale = scale;
+            self.last_camera = data.camera;
         }
 
-        let flipy = *data.flipy.lock().unwrap();
-        let shift = data.shift.lock().unwrap().clone();
+        let flipy = data.header.flipy;
+        let shift = data.header.shift;
 
+        let frame = data.frames.get(data.frame);
+        let unmatched = match (&frame, self.pane) {
+            (Some(frame), Some(pane)) if data.panes.len() > 1 => self.unmatched.get(frame, &data.panes, pane, data.layers),
+            _ => Arc::default(),
+        };
         let transform = Transform::new(|p| self.transform(p), shift, data_size, flipy);
 
-        let enabled_tags = data
-            .tags
-            .lock()
-            .unwrap()
-            .iter()
-            .filter(|(_, b)| *b)
-            .map(|(tag, _)| tag.clone())
-            .collect::<HashSet<String>>();
-
-        if data.frame < data.frames.lock().unwrap().len() {
-            let frame = &data.frames.lock().unwrap()[data.frame];
-            for ind in frame.iter() {
-                let item = &data.objects.lock().unwrap()[*ind];
+        let enabled_tags = enabled_tags(self.tags(data));
+
+        if let Some(frame) = frame {
+            let mut visible = Vec::new();
+            frame.index.query(self.visible_rect(shift, data_size, flipy), &mut visible);
+            visible.sort_unstable();
+
+            let mut outlines = Vec::new();
+            for pos in visible {
+                let item = &frame.objects[pos];
                 if item.need_to_draw(&enabled_tags) {
-                    item.draw(ctx, self.scale, &transform);
+                    item.draw(ctx, self.scale, &transform, env);
+                    let id = item.common().id.map(|id| merge::source_id(id, data.layers));
+                    if let Some(bounds) = item.bounds().filter(|_| id.is_some_and(|id| unmatched.contains(&id))) {
+                        let (start, end) = (transform.point(bounds.origin()), transform.point(Point::new(bounds.x1, bounds.y1)));
+                        outlines.push(Rect::from_points(start, end).inflate(DIFF_MARGIN, DIFF_MARGIN));
+                    }
                 }
             }
+            for outline in outlines {
+                ctx.stroke(outline, &env.get(theme::DIFF_COLOR), 2.0);
+            }
+        }
+
+        let region = match self.selection {
+            Some((start, end)) => Some(Rect::from_points(start, end)),
+            None => self
+                .export_region
+                .map(|region| Rect::from_points(self.transform(region.origin()), self.transform(Point::new(region.x1, region.y1)))),
+        };
+        if let Some(region) = region {
+            let style = StrokeStyle::new().dash_pattern(&[6.0, 4.0]);
+            ctx.stroke_styled(region, &env.get(theme::OVERLAY_TEXT_COLOR), 1.0, &style);
+        }
+
+        if self.reloaded_timer != TimerToken::INVALID {
+            let layout = ctx
+                .text()
+                .new_text_layout("reloaded")
+                .font(FontFamily::SANS_SERIF, 11.0)
+                .text_color(env.get(theme::OVERLAY_TEXT_COLOR))
+                .build()
+                .unwrap();
+            let size = layout.size();
+            ctx.draw_text(
+                &layout,
+                Point::new(self.size.width - size.width - PADDING, self.size.height - size.height - PADDING),
+            );
         }
 
         // let text = ctx.text();
@@ -480,292 +453,396 @@ impl Widget<AppData> for DrawingWidget {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect::<Vec<String>>()[1..].to_vec();
-
-    let mut init_frames: Vec<usize> = Vec::new();
-
-    let objects = Arc::new(Mutex::new(Vec::new()));
-    let objects_ptr = objects.clone();
-
-    let frames = Arc::new(Mutex::new(Vec::new()));
-    let frames_ptr = frames.clone();
-
-    let tags = Arc::new(Mutex::new(Vec::new()));
-    let tags_ptr = tags.clone();
-
-    let draw_properties = Arc::new(Mutex::new(DrawProperties {
-        width: 1.0,
-        font: 1.0,
-        was_messages: 0,
-    }));
-    let draw_properties_ptr = draw_properties.clone();
-
-    let fps_speed = Arc::new(Mutex::new(0.033));
-    let fps_speed_ptr = fps_speed.clone();
-
-    let size = Arc::new(Mutex::new(Size::new(10.0, 10.0)));
-    let size_ptr = size.clone();
-
-    let finished = Arc::new(Mutex::new(false));
-    let finished_ptr = finished.clone();
-
-    let svg_width_scale = Arc::new(Mutex::new(0.3));
-    let svg_width_scale_ptr = svg_width_scale.clone();
-
-    let flipy = Arc::new(Mutex::new(false));
-    let flipy_ptr = flipy.clone();
-
-    let shift = Arc::new(Mutex::new(Size::new(0.0, 0.0)));
-    let shift_ptr = shift.clone();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match cli::parse(&args) {
+        Ok(cli::Command::View(input)) => view(input, 0),
+        Ok(cli::Command::Export(input, args)) => {
+            if !export_headless(input, args) {
+                process::exit(1);
+            }
+        }
+        Ok(cli::Command::Validate(path)) => {
+            let valid = match &path {
+                Some(path) => File::open(path).and_then(|file| validate::validate(io::BufReader::new(file), path)),
+                None => validate::validate(io::stdin().lock(), "stdin"),
+            };
+            match valid {
+                Ok(true) => {}
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    eprintln!("Can't read {}, {}", path.as_deref().unwrap_or("stdin"), e);
+                    process::exit(1);
+                }
+            }
+        }
+        Ok(cli::Command::Stats(input, json)) => {
+            let start = Instant::now();
+            let data = read_all(input).unwrap_or_else(|| process::exit(1));
+            let stats = stats::collect(&data.frames, start);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+            } else {
+                stats.print();
+            }
+        }
+        Ok(cli::Command::Slice(path, args)) => {
+            if !slice_replay(path.as_deref(), args) {
+                process::exit(1);
+            }
+        }
+        Ok(cli::Command::Merge(paths, layers, output)) => {
+            if !merge_replays(&paths, layers, output.as_deref()) {
+                process::exit(1);
+            }
+        }
+        Ok(cli::Command::Diff(input, json, open)) => {
+            if !diff_replays(input, json, open) {
+                process::exit(1);
+            }
+        }
+        Ok(cli::Command::Config) => {
+            // the settings go to the standard output, so they can be saved as a config
+            for path in settings::config_paths() {
+                let found = if path.is_file() { "" } else { " (not found)" };
+                eprintln!("{}{}", path.display(), found);
+            }
+            println!("{}", serde_json::to_string_pretty(&get_settings()).unwrap());
+        }
+        Ok(cli::Command::Help) => print!("{}", cli::USAGE),
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(1);
+        }
+    }
+}
 
-    let handle = thread::spawn(move || {
-        let app_data = AppData {
-            objects: objects_ptr,
-            frames: frames_ptr,
-            frame: 0,
-            fps_speed: fps_speed_ptr,
-            size: size_ptr,
-            tags: tags_ptr,
-            draw_properties: draw_properties_ptr,
-            svg_width_scale: svg_width_scale_ptr,
-            flipy: flipy_ptr,
-            shift: shift_ptr,
-            finished: finished_ptr,
+fn view(input: cli::Input, frame: usize) {
+    let mut data = AppData::new(input.export);
+    data.frame = frame;
+    data.following = input.follow;
+    data.layers = input.layers.len();
+    if input.compare {
+        // a pane for each of overlaid replays, or two of the same one
+        let panes = match input.layers.len() {
+            0 => vec![Pane::new(None), Pane::new(None)],
+            _ => input.layers.iter().map(|layer| Pane::new(Some(layer.tag("")))).collect(),
         };
+        data.panes = Arc::new(panes);
+    }
 
-        let window = WindowDesc::new(make_layout())
-            .window_size(Size { width: 800.0, height: 600.0 })
-            .menu(make_menu)
-            .resizable(true)
-            .title("Viewer");
-        AppLauncher::with_window(window).launch(app_data).expect("launch failed");
+    let width = if data.panes.is_empty() { 800.0 } else { 1200.0 };
+    let window = WindowDesc::new(make_layout(data.panes.len()))
+        .window_size(Size { width, height: 600.0 })
+        .menu(make_menu)
+        .resizable(true)
+        .title("Viewer");
+    let theme = Theme::from_settings(&get_settings());
+    let launcher = AppLauncher::with_window(window)
+        .delegate(Delegate)
+        .configure_env(move |env, _| theme.configure_env(env));
+    let sink = launcher.get_external_handle();
+
+    let paths = input.paths.clone();
+    let layers = input.layers.clone();
+    let lazy = input.lazy;
+    let follow = input.follow;
+    // the standard input can't be read again, a followed file is read as it changes anyway
+    let auto_reload = get_settings().auto_reload.unwrap() && !paths.is_empty() && !follow;
+    thread::spawn(move || {
+        // fails only if the window is already closed, the app is exiting then
+        let publish = |update: LoadUpdate| {
+            let _ = sink.submit_command(LOAD_UPDATE, SingleUse::new(update), Target::Auto);
+        };
+        if follow {
+            let restart = || {
+                let _ = sink.submit_command(RELOAD, SingleUse::new(LoadUpdate::default()), Target::Auto);
+            };
+            if let Err(e) = loader::follow(Path::new(&paths[0]), publish, restart) {
+                eprintln!("Can't read {}, {}", paths[0], e);
+            }
+            return;
+        }
+        let mut seen = watch::file_states(&paths);
+        if let Err(e) = load(&paths, layers.clone(), lazy, publish) {
+            eprintln!("Can't read {}, {}", input_name(&paths), e);
+        }
+        if !auto_reload {
+            return;
+        }
+        loop {
+            seen = watch::wait_for_change(&paths, &seen);
+            // sent at once, so that the old frames are shown until the new ones are ready
+            let mut reloaded = LoadUpdate::default();
+            if let Err(e) = load(&paths, layers.clone(), lazy, |update| reloaded.extend(update)) {
+                eprintln!("Can't read {}, {}", input_name(&paths), e);
+                continue;
+            }
+            if sink.submit_command(RELOAD, SingleUse::new(reloaded), Target::Auto).is_err() {
+                break;
+            }
+        }
     });
 
-    let iter: Box<dyn BufRead> = if args.len() > 0 {
-        Box::new(io::BufReader::new(File::open(&args[0]).unwrap()))
-    } else {
-        Box::new(io::BufReader::new(io::stdin()))
-    };
-
-    let mut tags_set: HashSet<String> = HashSet::new();
-    let mut disabled_tags: HashSet<String> = HashSet::new();
-
-    let mut unparsed = 0;
+    launcher.launch(data).expect("launch failed");
+}
 
-    let mut last_frame = Vec::new();
-    let mut is_initial_tick = true;
+// the same pipeline as in the window, but the replay is read completely before the export starts
+fn export_headless(input: cli::Input, args: cli::ExportArgs) -> bool {
+    let mut data = match read_all(input) {
+        Some(data) => data,
+        None => return false,
+    };
+    if let Some(tags) = &args.tags {
+        for tag in tags.iter().filter(|tag| !data.tags.iter().any(|(name, _)| name == *tag)) {
+            eprintln!("No objects with tag {}", tag);
+        }
+        for (tag, enabled) in Arc::make_mut(&mut data.tags).iter_mut() {
+            *enabled = tags.contains(tag);
+        }
+    }
 
-    let mut in_between_properties = InBetweenProperties::new();
+    let mut options = ExportOptions::new(&get_settings(), None);
+    options.width = args.width;
+    let export = match args.format.as_str() {
+        "png" => export::save_all_frames_as_png(&data, options, None),
+        "svg" => export::save_all_frames_as_svg(&data, options, None),
+        "gif" => export::export_animation(&data, options, Format::Gif, None),
+        "apng" => export::export_animation(&data, options, Format::Apng, None),
+        "animated-svg" => export::export_animated_svg(&data, options, None),
+        "html" => export::export_html(&data, options, None),
+        "sheet-png" => export::export_contact_sheet(&data, options, "png", None),
+        "sheet-svg" => export::export_contact_sheet(&data, options, "svg", None),
+        "mp4" => export::make_video_from_frames(&data, None),
+        _ => unreachable!(),
+    };
+    match export.map(|(_, thread)| thread.join()) {
+        Some(Ok(status)) => status.errors.is_empty() && !status.is_cancelled(),
+        _ => false,
+    }
+}
 
-    let add_frame = |frames: &Arc<Mutex<Vec<Vec<usize>>>>, frame: &Vec<usize>, in_between_properties: &InBetweenProperties| {
-        if in_between_properties.frames == 1 || frames.lock().unwrap().is_empty() {
-            frames.lock().unwrap().push(frame.clone());
-        } else {
-            let mut frames = frames.lock().unwrap();
-            let mut prev = HashMap::new();
-            let mut in_between_frames = vec![Vec::new(); in_between_properties.frames - 1];
-            for &item in frames.last().unwrap() {
-                if let Some(id) = objects.lock().unwrap()[item].common().id {
-                    prev.insert(id, item);
-                } else {
-                    for frame in in_between_frames.iter_mut() {
-                        frame.push(item);
-                    }
-                }
-            }
-            for &item in frame.iter() {
-                let mut objects = objects.lock().unwrap();
-                if let Some(id) = objects[item].common().id {
-                    if let Some(&i) = prev.get(&id) {
-                        let v = in_betweens(&*objects[i], &*objects[item], &in_between_properties);
-                        if v.is_empty() {
-                            continue;
-                        }
-                        assert_eq!(v.len(), in_between_properties.frames - 1);
-                        for (i, x) in v.into_iter().enumerate() {
-                            let len = objects.len();
-                            objects.push(x);
-                            in_between_frames[i].push(len);
-                        }
-                    }
-                }
+fn slice_replay(path: Option<&str>, args: cli::SliceArgs) -> bool {
+    let reader: Box<dyn io::BufRead> = match path {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Can't read {}, {}", path, e);
+                return false;
             }
-            frames.extend(in_between_frames);
-            frames.push(frame.clone());
-        }
+        },
+        None => Box::new(io::stdin().lock()),
+    };
+    let writer = match create_output(args.output.as_deref()) {
+        Some(writer) => writer,
+        None => return false,
     };
+    match slice::slice(reader, writer, args.selection) {
+        Ok(ticks) => {
+            eprintln!("{} ticks written", ticks);
+            true
+        }
+        Err(e) => {
+            eprintln!("Can't slice {}, {}", path.unwrap_or("stdin"), e);
+            false
+        }
+    }
+}
 
-    for line in iter.lines() {
-        let line = line.unwrap();
-        if line.starts_with("tick") {
-            if is_initial_tick {
-                init_frames = last_frame.clone();
-            } else {
-                add_frame(&frames, &last_frame, &in_between_properties);
+// the standard output if there is no path
+fn create_output(path: Option<&str>) -> Option<Box<dyn io::Write>> {
+    match path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(Box::new(io::BufWriter::new(file))),
+            Err(e) => {
+                eprintln!("Can't create {}, {}", path, e);
+                None
             }
-            last_frame = init_frames.clone();
-            print!("\rreading tick {}", frames.lock().unwrap().len() + 1);
-            draw_properties.lock().unwrap().was_messages = 0;
-            io::stdout().flush().unwrap();
-            is_initial_tick = false;
-        } else if line.starts_with("speed") {
-            *fps_speed.lock().unwrap() = 1.0 / line[6..].trim().parse::<f64>().unwrap();
-        } else if line.starts_with("width") {
-            draw_properties.lock().unwrap().width = line[6..].trim().parse().unwrap();
-        } else if line.starts_with("font") {
-            draw_properties.lock().unwrap().font = line[5..].trim().parse().unwrap();
-        } else if line.starts_with("size") {
-            let line = line.trim();
-            let mut iter = line.trim()[6..line.len() - 1].split(",");
-            size.lock().unwrap().width = iter.next().unwrap().parse().unwrap();
-            size.lock().unwrap().height = iter.next().unwrap().parse().unwrap();
-        } else if line.starts_with("shift") {
-            let line = line.trim();
-            let mut iter = line.trim()[7..line.len() - 1].split(",");
-            shift.lock().unwrap().width = iter.next().unwrap().parse().unwrap();
-            shift.lock().unwrap().height = iter.next().unwrap().parse().unwrap();
-        } else if line.starts_with("svgwidth") {
-            *svg_width_scale.lock().unwrap() = line[9..].trim().parse::<f64>().unwrap();
-        } else if line.starts_with("flipy") {
-            *flipy.lock().unwrap() = true;
-        } else if line.starts_with("disable ") {
-            let dtag = line[8..].trim().to_string();
-            for (tag, b) in tags.lock().unwrap().iter_mut() {
-                if *tag == dtag {
-                    *b = false;
-                }
+        },
+        None => Some(Box::new(io::BufWriter::new(io::stdout().lock()))),
+    }
+}
+
+fn merge_replays(paths: &[String], layers: Vec<Layer>, output: Option<&str>) -> bool {
+    let mut readers = Vec::new();
+    for path in paths {
+        match File::open(path) {
+            Ok(file) => readers.push(io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Can't read {}, {}", path, e);
+                return false;
             }
-            disabled_tags.insert(dtag);
-        } else if line.starts_with("in_betweens") {
-            in_between_properties.frames = line[12..].parse().unwrap();
-            in_between_properties.func = (1..in_between_properties.frames)
-                .map(|x| x as f64 / in_between_properties.frames as f64)
-                .collect();
-            in_between_properties.funcs.insert("line".to_string(), in_between_properties.func.clone());
-        } else if line.starts_with("setfunc") {
-            let mut iter = line.split_whitespace().skip(1);
-            let name = iter.next().unwrap().to_string();
-            let values = iter.map(|s| s.parse().unwrap()).collect();
-            in_between_properties.funcs.insert(name, values);
-        } else {
-            match figure::from_string(&line, &mut draw_properties.lock().unwrap()) {
-                Some(x) => {
-                    for tag in x.tags().iter() {
-                        if !tags_set.contains(tag) {
-                            tags_set.insert(tag.clone());
-                            tags.lock().unwrap().push((tag.clone(), !disabled_tags.contains(tag)));
-                        }
-                    }
-                    last_frame.push(objects.lock().unwrap().len());
-                    if x.keep() {
-                        init_frames.push(objects.lock().unwrap().len());
-                    }
-                    objects.lock().unwrap().push(x);
-                }
-                None => unparsed += 1,
-            };
         }
     }
-    add_frame(&frames, &last_frame, &in_between_properties);
+    let writer = match create_output(output) {
+        Some(writer) => writer,
+        None => return false,
+    };
+    match merge::write(readers, layers, writer) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Can't merge {}, {}", input_name(paths), e);
+            false
+        }
+    }
+}
 
-    *finished.lock().unwrap() = true;
+// true if the replays are the same, with `open` the window is shown where they differ
+fn diff_replays(mut input: cli::Input, json: bool, open: bool) -> bool {
+    let mut replays = Vec::new();
+    for path in input.paths.iter() {
+        let mut data = AppData::new(input.export.clone());
+        if let Err(e) = load(std::slice::from_ref(path), Vec::new(), input.lazy, |update| data.apply(update)) {
+            eprintln!("Can't read {}, {}", path, e);
+            return false;
+        }
+        replays.push(data);
+    }
+    let diff = diff::compare(&replays[0].frames, &replays[1].frames);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+    } else {
+        diff.print([&input.paths[0], &input.paths[1]]);
+    }
+    match &diff.first {
+        Some(first) if open => {
+            input.compare = true;
+            view(input, first.frame - 1);
+            false
+        }
+        first => first.is_none(),
+    }
+}
 
-    println!("");
-    println!("unparsed: {}", unparsed);
+// the whole replay, for commands without a window
+fn read_all(input: cli::Input) -> Option<AppData> {
+    let mut data = AppData::new(input.export);
+    match load(&input.paths, input.layers, input.lazy, |update| data.apply(update)) {
+        Ok(()) => Some(data),
+        Err(e) => {
+            eprintln!("Can't read {}, {}", input_name(&input.paths), e);
+            None
+        }
+    }
+}
 
-    // app_data.objects = Rc::new(objects);
-    // app_data.frame = app_data.frame.min(frames.len() - 1);
-    // app_data.frames = Rc::new(frames);
-    // let mut tags = tags.iter().map(|x| (x.clone(), true)).collect::<Vec<_>>();
-    // tags.sort();
-    // app_data.tags = Arc::new(Mutex::new(tags));
+fn input_name(paths: &[String]) -> String {
+    match paths {
+        [] => "stdin".to_string(),
+        paths => paths.join(", "),
+    }
+}
 
-    handle.join().unwrap();
+// updates are published as the replay is read, until it is finished
+fn load(paths: &[String], layers: Vec<Layer>, lazy: bool, publish: impl FnMut(LoadUpdate)) -> io::Result<()> {
+    match paths {
+        [path] if lazy || fs::metadata(path)?.len() > LAZY_LOADING_THRESHOLD => frame_index::open(Path::new(path), publish),
+        [path] => {
+            let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+            loader::read_parallel(io::BufReader::with_capacity(1 << 20, File::open(path)?), threads, publish)
+        }
+        [] => {
+            loader::read(io::BufReader::with_capacity(1 << 20, io::stdin()), publish);
+            Ok(())
+        }
+        // overlaid replays are read tick by tick, so they are never lazy
+        paths => {
+            let readers = paths.iter().map(File::open).map(|file| file.map(io::BufReader::new));
+            loader::read_merged(readers.collect::<io::Result<_>>()?, layers, publish)
+        }
+    }
 }
 
 fn make_menu(_id: Option<WindowId>, _data: &AppData, _env: &Env) -> Menu<AppData> {
     Menu::new("my title")
-        .entry(MenuItem::new("Save frame as svg").command(Command::new(Selector::new("save_frame_as_svg"), (), Target::Auto)))
-        .entry(MenuItem::new("Save frame as png").command(Command::new(Selector::new("save_frame_as_png"), (), Target::Auto)))
-        .entry(MenuItem::new("Save all frames as svg").command(Command::new(Selector::new("save_all_frames_as_svg"), (), Target::Auto)))
-        .entry(MenuItem::new("Save all frames as png").command(Command::new(Selector::new("save_all_frames_as_png"), (), Target::Auto)))
-        .entry(MenuItem::new("Make video from frames").command(Command::new(Selector::new("make_video_from_frames"), (), Target::Auto)))
+        .entry(export_item("Save frame as svg", "save_frame_as_svg"))
+        .entry(export_item("Save frame as png", "save_frame_as_png"))
+        .entry(export_item("Save all frames as svg", "save_all_frames_as_svg"))
+        .entry(export_item("Save all frames as png", "save_all_frames_as_png"))
+        .entry(export_item("Export animation as gif", "export_gif"))
+        .entry(export_item("Export animation as apng", "export_apng"))
+        .entry(export_item("Export animation as svg", "export_animated_svg"))
+        .entry(export_item("Export replay as html", "export_html"))
+        .entry(export_item("Export contact sheet as svg", "export_sheet_svg"))
+        .entry(export_item("Export contact sheet as png", "export_sheet_png"))
+        .entry(MenuItem::new("Export visible area only").command(Command::new(Selector::new("export_visible_area"), (), Target::Auto)))
+        .entry(MenuItem::new("Export whole frame").command(Command::new(Selector::new("reset_export_region"), (), Target::Auto)))
+        .entry(MenuItem::new("Export settings").command(Command::new(Selector::new("open_export_settings"), (), Target::Auto)))
+        .entry(export_item("Make video from frames", "make_video_from_frames"))
+}
+
+// only one export runs at a time
+fn export_item(title: &str, command: &'static str) -> MenuItem<AppData> {
+    MenuItem::new(title)
+        .command(Command::new(Selector::new(command), (), Target::Auto))
+        .enabled_if(|data: &AppData, _env| !data.is_exporting())
 }
 
-fn make_layout() -> impl Widget<AppData> {
+// a drawing widget with its tags on the right
+fn make_view(pane: Option<usize>) -> impl Widget<AppData> {
     let drawing_widget_id = WidgetId::next();
+    let tags = lens::Map::new(
+        move |data: &AppData| match pane {
+            Some(pane) => data.panes[pane].tags.clone(),
+            None => data.tags.clone(),
+        },
+        move |data: &mut AppData, tags| match pane {
+            Some(pane) if !Arc::ptr_eq(&data.panes[pane].tags, &tags) => Arc::make_mut(&mut data.panes)[pane].tags = tags,
+            Some(_) => {}
+            None => data.tags = tags,
+        },
+    );
+
+    Flex::row()
+        .with_flex_child(DrawingWidget::new(pane).with_id(drawing_widget_id), 1.0)
+        .with_spacer(PADDING)
+        .with_child(Checklist::new(Some(drawing_widget_id)).lens(tags))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+}
+
+// `panes` views side by side, or just one view if there are none
+fn make_layout(panes: usize) -> impl Widget<AppData> {
+    let mut views = Flex::row();
+    if panes == 0 {
+        views.add_flex_child(make_view(None), 1.0);
+    }
+    for pane in 0..panes {
+        if pane > 0 {
+            views.add_spacer(PADDING);
+        }
+        views.add_flex_child(make_view(Some(pane)), 1.0);
+    }
 
     Flex::column()
-        .with_flex_child(
-            Flex::row()
-                .with_flex_child(
-                    DrawingWidget {
-                        scale: 1.0,
-                        center: Point::new(0.0, 0.0),
-                        size: Size::new(1.0, 1.0),
-                        last_mouse_pos: Point::new(0.0, 0.0),
-                        mouse_down: false,
-                        timer_id: TimerToken::INVALID,
-                        running: false,
-                        last_data_size: Size::new(0.0, 0.0),
-                    }
-                    .with_id(drawing_widget_id),
-                    1.0,
-                )
-                .with_spacer(PADDING)
-                .with_child(Checklist::new(Some(drawing_widget_id)).lens(AppData::tags))
-                .cross_axis_alignment(CrossAxisAlignment::Start),
-            1.0,
-        )
+        .with_flex_child(views.cross_axis_alignment(CrossAxisAlignment::Start), 1.0)
         .with_spacer(PADDING)
         .with_child(
             Flex::row()
                 .with_flex_child(SizedBox::new(ISlider::new().with_range(0, 10)).expand_width(), 1.0)
                 .with_child(
                     SizedBox::new(Align::right(Label::new(|data: &AppData, _env: &_| {
-                        format!("{} / {}", data.frame + 1, data.frames.lock().unwrap().len())
+                        format!("{} / {}", data.frame + 1, data.frames.len())
                     })))
                     .width(100.0),
                 ),
         )
+        .with_spacer(PADDING)
+        .with_child(
+            Flex::row()
+                .with_flex_child(
+                    Label::new(|data: &AppData, _env: &_| data.job.as_ref().map_or(String::new(), JobStatus::text)).expand_width(),
+                    1.0,
+                )
+                .with_child(Either::new(
+                    |data: &AppData, _env| data.is_exporting(),
+                    Button::new("Cancel").on_click(|_ctx, data: &mut AppData, _env| {
+                        if let Some(job) = &data.job {
+                            job.cancel();
+                        }
+                    }),
+                    SizedBox::empty(),
+                )),
+        )
         .cross_axis_alignment(CrossAxisAlignment::Start)
         .main_axis_alignment(MainAxisAlignment::End)
         .padding(PADDING)
 }
-
-fn get_settings() -> Settings {
-    let mut path = std::env::current_exe().unwrap();
-    path.pop();
-    path.push("settings.json");
-    let path = path.as_path();
-    if !path.is_file() {
-        std::fs::File::create(path).unwrap().write_all("{}".to_string().as_bytes()).unwrap();
-    }
-    let mut settings: Settings = match serde_json::from_str(&fs::read_to_string(path).unwrap()) {
-        Ok(x) => x,
-        Err(_) => {
-            eprintln!("Can't parse json from \"settings.json\"");
-            std::process::exit(1);
-        }
-    };
-
-    if settings.conversion_tool.is_none() {
-        settings.conversion_tool = Some("rsvg-convert".to_string());
-    }
-    if settings.inkscape_path.is_none() {
-        settings.inkscape_path = Some("C:/Program files/Inkscape/bin/inkscape.exe".to_string());
-    }
-    if settings.frame_resolution.is_none() {
-        settings.frame_resolution = Some(1080);
-    }
-    if settings.max_threads.is_none() {
-        settings.max_threads = Some(4);
-    }
-
-    std::fs::File::create(path)
-        .unwrap()
-        .write_all(serde_json::to_string_pretty(&settings).unwrap().as_bytes())
-        .unwrap();
-
-    settings
-}
@@ -0,0 +1,146 @@
+use crate::app_data::DrawProperties;
+use crate::figure::CommonParams;
+use crate::in_between::interpolate;
+use crate::loader::{parse_line, Line, Setting};
+
+use std::io::{self, BufRead, Write};
+
+use druid::Color;
+
+// how much of the object color is replaced by the tint
+const TINT: f64 = 0.5;
+
+/// One of the replays merged into one view. Its tags are shown under `name/`,
+/// objects without tags get the tag `name/` itself.
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub tint: Option<Color>,
+    pub opacity: f64,
+    // ids of different replays must not match, or they would be in-betweened together
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Layer {
+    /// Layers named A, B, C and so on.
+    pub fn new(index: usize, count: usize) -> Self {
+        Self {
+            name: ((b'A' + index as u8) as char).to_string(),
+            tint: None,
+            opacity: 1.0,
+            index,
+            count,
+        }
+    }
+
+    pub fn tag(&self, tag: &str) -> String {
+        format!("{}/{}", self.name, tag)
+    }
+
+    pub fn apply(&self, common: &mut CommonParams) {
+        if common.tags.is_empty() {
+            common.tags.push(self.tag(""));
+        } else {
+            common.tags = common.tags.iter().map(|tag| self.tag(tag)).collect();
+        }
+        common.id = common.id.map(|id| id.wrapping_mul(self.count as i32).wrapping_add(self.index as i32));
+        common.func = common.func.as_ref().map(|func| self.tag(func));
+
+        let alpha = common.color.as_rgba().3;
+        if let Some(tint) = &self.tint {
+            common.color = interpolate(&common.color, tint, TINT);
+        }
+        common.color = common.color.clone().with_alpha(alpha * self.opacity);
+    }
+}
+
+/// Id of an object in its own replay, `count` replays are overlaid.
+pub fn source_id(id: i32, count: usize) -> i32 {
+    if count > 1 {
+        id.div_euclid(count as i32)
+    } else {
+        id
+    }
+}
+
+struct Source<R> {
+    reader: R,
+    draw_properties: DrawProperties,
+    finished: bool,
+}
+
+/// Reads the replays tick by tick and passes their lines on as one replay, the first replay sets
+/// the size, speed and other settings of the whole replay. A replay that ends earlier just stops.
+pub fn merge<R: BufRead>(readers: Vec<R>, layers: Vec<Layer>, mut push: impl FnMut(Line)) -> io::Result<()> {
+    let mut sources = readers
+        .into_iter()
+        .zip(layers)
+        .map(|(reader, layer)| {
+            let mut draw_properties = DrawProperties::new();
+            draw_properties.layer = Some(layer);
+            Source {
+                reader,
+                draw_properties,
+                finished: false,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut line = String::new();
+    loop {
+        // messages of all replays are listed one after another
+        let mut messages = 0;
+        for (ind, source) in sources.iter_mut().enumerate().filter(|(_, source)| !source.finished) {
+            loop {
+                line.clear();
+                if source.reader.read_line(&mut line)? == 0 {
+                    source.finished = true;
+                    break;
+                }
+                source.draw_properties.was_messages = messages;
+                let parsed = parse_line(line.trim_end_matches(&['\n', '\r'][..]), &mut source.draw_properties);
+                messages = source.draw_properties.was_messages;
+                match parsed {
+                    Line::Tick => break,
+                    // already applied to the following objects
+                    Line::Setting(Setting::Width(_) | Setting::Font(_)) => {}
+                    // these are named after the layer, the rest only comes from the first replay
+                    Line::Setting(setting @ (Setting::Disable(_) | Setting::SetFunc(..))) => push(Line::Setting(setting)),
+                    Line::Setting(_) if ind > 0 => {}
+                    parsed => push(parsed),
+                }
+            }
+        }
+        if sources.iter().all(|source| source.finished) {
+            return Ok(());
+        }
+        push(Line::Tick);
+    }
+}
+
+/// Writes the merged replay as a new replay file.
+pub fn write<R: BufRead, W: Write>(readers: Vec<R>, layers: Vec<Layer>, mut writer: W) -> io::Result<()> {
+    let mut result = Ok(());
+    let mut unparsed = 0;
+    merge(readers, layers, |line| {
+        let line = match line {
+            Line::Tick => "tick".to_string(),
+            Line::Setting(setting) => setting.to_line(),
+            Line::Object(x) => x.to_line(),
+            Line::Unparsed => {
+                unparsed += 1;
+                return;
+            }
+        };
+        if result.is_ok() {
+            result = writeln!(writer, "{}", line);
+        }
+    })?;
+    result?;
+    writer.flush()?;
+    if unparsed > 0 {
+        eprintln!("unparsed: {}", unparsed);
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use druid::{Color, Rect};
+
+use svg::node::element::Text as SvgText;
+use svg::node::Text as SvgText2;
+use svg::Document;
+
+// text height relative to the image width, about the same as in the viewer for 600px wide images
+const FONT_SCALE: f64 = 1.0 / 60.0;
+
+pub fn color_to_string(color: &Color) -> String {
+    let (r, g, b, _a) = color.as_rgba8();
+    format!("rgb({}, {}, {})", r, g, b)
+}
+
+pub fn font_size(region: Rect) -> f64 {
+    region.width() * FONT_SCALE
+}
+
+/// Frame number and enabled tags in the top right corner.
+pub fn legend(mut img: Document, region: Rect, color: &Color, frame: usize, total_frames: usize, tags: &[(String, bool)]) -> Document {
+    let font = font_size(region);
+    let lines = std::iter::once(format!("{} / {}", frame + 1, total_frames)).chain(tags.iter().filter(|(_, b)| *b).map(|(tag, _)| tag.clone()));
+    for (ind, line) in lines.enumerate() {
+        let text = SvgText::new()
+            .add(SvgText2::new(line))
+            .set("x", region.x1 - font * 0.5)
+            .set("y", region.y0 + font * 1.2 * (ind + 1) as f64)
+            .set("fill", color_to_string(color))
+            .set("font-size", font)
+            .set("text-anchor", "end")
+            .set("font-family", "monospace");
+        img = img.add(text);
+    }
+    img
+}
@@ -0,0 +1,136 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+// results computed ahead of the consumer, per thread
+const AHEAD_PER_THREAD: usize = 2;
+
+struct Queue<I> {
+    items: I,
+    taken: usize,
+    consumed: usize,
+    stopped: bool,
+}
+
+/// Results of a function applied to items on several threads, in the order of the items.
+/// Threads don't get too far ahead of the consumer, so only a few results are kept in memory.
+/// Dropping it stops the threads after their current items.
+/// A panic in `f` ends the results early with an error in place of the panicked item.
+pub struct OrderedMap<I, R> {
+    queue: Arc<(Mutex<Queue<I>>, Condvar)>,
+    results: Receiver<(usize, Result<R, String>)>,
+    pending: BTreeMap<usize, Result<R, String>>,
+    next: usize,
+    len: usize,
+}
+
+pub fn ordered_map<I, R, F>(items: I, threads: usize, f: F) -> OrderedMap<I, R>
+where
+    I: ExactSizeIterator + Send + 'static,
+    I::Item: Send,
+    R: Send + 'static,
+    F: Fn(I::Item) -> R + Send + Sync + 'static,
+{
+    let threads = threads.max(1);
+    let len = items.len();
+    let queue = Arc::new((
+        Mutex::new(Queue {
+            items,
+            taken: 0,
+            consumed: 0,
+            stopped: false,
+        }),
+        Condvar::new(),
+    ));
+    let (sender, results) = channel();
+    let f = Arc::new(f);
+    for _ in 0..threads {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        let f = f.clone();
+        thread::spawn(move || loop {
+            let (ind, item) = {
+                let (lock, cvar) = &*queue;
+                let mut queue = cvar
+                    .wait_while(lock.lock().unwrap(), |queue| {
+                        !queue.stopped && queue.taken >= queue.consumed + threads * AHEAD_PER_THREAD
+                    })
+                    .unwrap();
+                if queue.stopped {
+                    return;
+                }
+                match queue.items.next() {
+                    Some(item) => {
+                        queue.taken += 1;
+                        (queue.taken - 1, item)
+                    }
+                    None => return,
+                }
+            };
+            // the results end with a panicked item
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(item))).map_err(|e| panic_message(&*e));
+            if result.is_err() {
+                let (lock, cvar) = &*queue;
+                lock.lock().unwrap().stopped = true;
+                cvar.notify_all();
+            }
+            if sender.send((ind, result)).is_err() {
+                return;
+            }
+        });
+    }
+
+    OrderedMap {
+        queue,
+        results,
+        pending: BTreeMap::new(),
+        next: 0,
+        len,
+    }
+}
+
+impl<I, R> Iterator for OrderedMap<I, R> {
+    type Item = Result<R, String>;
+
+    fn next(&mut self) -> Option<Result<R, String>> {
+        if self.next == self.len {
+            return None;
+        }
+        let result = loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                break result;
+            }
+            let (ind, result) = self.results.recv().ok()?;
+            self.pending.insert(ind, result);
+        };
+        // nothing comes after an error
+        self.next = if result.is_ok() { self.next + 1 } else { self.len };
+        let (lock, cvar) = &*self.queue;
+        lock.lock().unwrap().consumed = self.next;
+        cvar.notify_all();
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len - self.next))
+    }
+}
+
+impl<I, R> Drop for OrderedMap<I, R> {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.queue;
+        lock.lock().unwrap().stopped = true;
+        cvar.notify_all();
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown error".to_string(),
+    }
+}
@@ -27,6 +27,11 @@ impl<'a> Params<'a> {
     }
 }
 
+// values like `(10,20)`, a slice would panic on `(` alone
+fn items(s: &str) -> Option<std::str::Split<'_, char>> {
+    Some(s.strip_prefix('(')?.strip_suffix(')')?.split(','))
+}
+
 pub trait Param {
     fn from(s: &[&str]) -> Option<Self>
     where
@@ -81,24 +86,21 @@ impl Param for (char, char) {
 
 impl Param for (usize, usize) {
     fn from(s: &[&str]) -> Option<Self> {
-        let s = s[0];
-        let mut iter = s[1..s.len() - 1].split(',');
+        let mut iter = items(s[0])?;
         Some((iter.next()?.parse().ok()?, iter.next()?.parse().ok()?))
     }
 }
 
 impl Param for Point {
     fn from(s: &[&str]) -> Option<Self> {
-        let s = s[0];
-        let mut iter = s[1..s.len() - 1].split(',');
+        let mut iter = items(s[0])?;
         Some(Point::new(iter.next()?.parse().ok()?, iter.next()?.parse().ok()?))
     }
 }
 
 impl Param for (f64, f64) {
     fn from(s: &[&str]) -> Option<Self> {
-        let s = s[0];
-        let mut iter = s[1..s.len() - 1].split(',');
+        let mut iter = items(s[0])?;
         Some((iter.next()?.parse().ok()?, iter.next()?.parse().ok()?))
     }
 }
@@ -111,8 +113,7 @@ impl Param for Vec<Point> {
 
 impl Param for Color {
     fn from(s: &[&str]) -> Option<Self> {
-        let s = s[0];
-        let mut iter = s[1..s.len() - 1].split(',');
+        let mut iter = items(s[0])?;
         let r = iter.next()?.parse().ok()?;
         let g = iter.next()?.parse().ok()?;
         let b = iter.next()?.parse().ok()?;
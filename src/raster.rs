@@ -0,0 +1,65 @@
+use std::sync::{Arc, OnceLock};
+
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::fontdb::{self, FaceInfo, Family, Query, Style, Weight};
+use resvg::usvg::{Options, Tree};
+
+// loading system fonts is slow, so it is done once for all frames
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            // unknown families (like `system-ui` used for text) fall back to serif, which defaults
+            // to Times New Roman and is missing on most Linux systems
+            set_fallback(&mut fonts, Family::Serif, |_| true, |fonts, name| fonts.set_serif_family(name));
+            // same for monospace, which defaults to Courier New
+            set_fallback(
+                &mut fonts,
+                Family::Monospace,
+                |face| face.monospaced,
+                |fonts, name| fonts.set_monospace_family(name),
+            );
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+// a regular face of the installed fonts replaces the family if it has no fonts
+fn set_fallback(fonts: &mut fontdb::Database, family: Family, fits: impl Fn(&FaceInfo) -> bool, set: impl FnOnce(&mut fontdb::Database, String)) {
+    let query = Query {
+        families: &[family],
+        ..Default::default()
+    };
+    if fonts.query(&query).is_some() {
+        return;
+    }
+    let regular = fonts
+        .faces()
+        .filter(|face| fits(face) && face.weight == Weight::NORMAL && face.style == Style::Normal)
+        .find_map(|face| face.families.first())
+        .map(|(name, _)| name.clone());
+    if let Some(regular) = regular {
+        set(fonts, regular);
+    }
+}
+
+/// Rasterizes an svg image to the given width, keeping its aspect ratio.
+pub fn render(svg: &str, width: u32) -> Result<Pixmap, String> {
+    let options = Options {
+        fontdb: fonts(),
+        ..Default::default()
+    };
+    let tree = Tree::from_str(svg, &options).map_err(|e| e.to_string())?;
+    let size = tree.size();
+    let scale = width as f32 / size.width();
+    let height = (size.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = Pixmap::new(width, height).ok_or(format!("Can't create image {}x{}", width, height))?;
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+pub fn save_png(svg: &str, width: u32, file_name: &str) -> Result<(), String> {
+    render(svg, width)?.save_png(file_name).map_err(|e| e.to_string())
+}
@@ -0,0 +1,173 @@
+use crate::figure::Object;
+use crate::frame_index::FrameIndex;
+use crate::spatial_index::SpatialIndex;
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use druid::{Color, Point, Size};
+
+#[derive(Clone, PartialEq)]
+pub struct Header {
+    pub size: Size,
+    pub shift: Size,
+    pub flipy: bool,
+    pub fps_speed: f64,
+    pub svg_width_scale: f64,
+    // theme background is used if not set
+    pub background: Option<Color>,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            size: Size::new(10.0, 10.0),
+            shift: Size::new(0.0, 0.0),
+            flipy: false,
+            fps_speed: 0.033,
+            svg_width_scale: 0.3,
+            background: None,
+        }
+    }
+}
+
+impl Header {
+    /// Applies shift and flipy, as for exported images.
+    pub fn svg_transform(&self, mut p: Point) -> Point {
+        p.x += self.shift.width;
+        p.y += self.shift.height;
+        if self.flipy {
+            p.y = self.size.height - p.y;
+        }
+        p
+    }
+}
+
+pub struct Frame {
+    pub objects: Vec<Object>,
+    pub index: SpatialIndex,
+    // starting from 0, in-betweens have the tick they lead to
+    pub tick: usize,
+}
+
+impl Frame {
+    pub fn new(objects: Vec<Object>, tick: usize) -> Self {
+        let index = SpatialIndex::new(objects.iter().enumerate().map(|(pos, item)| (item.bounds(), pos)));
+        Self { objects, index, tick }
+    }
+}
+
+/// Frame borrowed from a shared chunk of frames.
+#[derive(Clone)]
+pub struct FrameRef {
+    chunk: Arc<[Frame]>,
+    pos: usize,
+}
+
+impl FrameRef {
+    pub fn new(chunk: Arc<[Frame]>, pos: usize) -> Self {
+        Self { chunk, pos }
+    }
+}
+
+impl Deref for FrameRef {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        &self.chunk[self.pos]
+    }
+}
+
+#[derive(Clone)]
+pub enum Frames {
+    /// All frames are parsed and kept in memory.
+    Loaded(Chunks),
+    /// Frames are parsed from the file on demand.
+    Indexed(Arc<FrameIndex>),
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Frames::Loaded(Chunks::default())
+    }
+}
+
+impl Frames {
+    pub fn len(&self) -> usize {
+        match self {
+            Frames::Loaded(chunks) => chunks.len(),
+            Frames::Indexed(index) => index.len(),
+        }
+    }
+
+    pub fn get(&self, ind: usize) -> Option<FrameRef> {
+        match self {
+            Frames::Loaded(chunks) => chunks.get(ind),
+            Frames::Indexed(index) => index.get(ind),
+        }
+    }
+
+    pub fn has_provisional(&self) -> bool {
+        matches!(self, Frames::Loaded(chunks) if chunks.provisional.is_some())
+    }
+
+    /// Index of the first frame of the tick, `len()` if there is no such tick yet.
+    pub fn first_frame_of_tick(&self, tick: usize) -> usize {
+        match self {
+            Frames::Loaded(chunks) => chunks.first_frame_of_tick(tick),
+            Frames::Indexed(index) => index.first_frame_of_tick(tick),
+        }
+    }
+}
+
+/// Append-only list of frames. Frames are published by the loader in immutable chunks,
+/// so cloning only copies pointers to them.
+#[derive(Clone, Default)]
+pub struct Chunks {
+    chunks: Vec<Arc<[Frame]>>,
+    starts: Vec<usize>,
+    len: usize,
+    // after the others, until they are replaced, see `LoadUpdate::provisional`
+    provisional: Option<Arc<[Frame]>>,
+}
+
+impl Chunks {
+    pub fn len(&self) -> usize {
+        self.len + self.provisional.as_ref().map_or(0, |frames| frames.len())
+    }
+
+    pub fn get(&self, ind: usize) -> Option<FrameRef> {
+        if ind >= self.len {
+            let provisional = self.provisional.as_ref()?;
+            return (ind - self.len < provisional.len()).then(|| FrameRef::new(provisional.clone(), ind - self.len));
+        }
+        let chunk = self.starts.partition_point(|&start| start <= ind) - 1;
+        Some(FrameRef::new(self.chunks[chunk].clone(), ind - self.starts[chunk]))
+    }
+
+    pub fn first_frame_of_tick(&self, tick: usize) -> usize {
+        let (mut first, mut last) = (0, self.len());
+        while first < last {
+            let mid = (first + last) / 2;
+            if self.get(mid).unwrap().tick < tick {
+                first = mid + 1;
+            } else {
+                last = mid;
+            }
+        }
+        first
+    }
+
+    pub fn push_chunk(&mut self, chunk: Arc<[Frame]>) {
+        if chunk.is_empty() {
+            return;
+        }
+        self.starts.push(self.len);
+        self.len += chunk.len();
+        self.chunks.push(chunk);
+    }
+
+    pub fn set_provisional(&mut self, frames: Vec<Frame>) {
+        self.provisional = Some(frames.into()).filter(|frames: &Arc<[Frame]>| !frames.is_empty());
+    }
+}
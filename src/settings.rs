@@ -1,9 +1,268 @@
+use crate::export_config::Overwrite;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Settings {
     pub conversion_tool: Option<String>,
     pub inkscape_path: Option<String>,
+    // in seconds, for the first attempt of rsvg-convert or inkscape, each next attempt gets this much more
+    pub conversion_timeout: Option<u64>,
+    pub conversion_attempts: Option<u64>,
     pub frame_resolution: Option<usize>,
     pub max_threads: Option<usize>,
+    pub animation_resolution: Option<usize>,
+    pub gif_palette_speed: Option<i32>,
+    pub export_messages: Option<bool>,
+    pub export_legend: Option<bool>,
+    pub theme: Option<String>,
+    // [r, g, b], override colors of the theme
+    pub canvas_color: Option<[u8; 3]>,
+    pub text_color: Option<[u8; 3]>,
+    pub diff_color: Option<[u8; 3]>,
+    pub transparent_background: Option<bool>,
+    // pixels per unit of the image, overrides resolutions
+    pub export_scale: Option<f64>,
+    // for `rviewer export` without --format
+    pub export_format: Option<String>,
+    pub output_dir: Option<String>,
+    // without extension, {name}, {frame} and {tick} are replaced, {frame:05} pads with zeros
+    pub file_template: Option<String>,
+    // numbers and "first..last" ranges separated by commas, starting from 1, ranges are inclusive and their ends optional
+    pub export_frames: Option<String>,
+    pub export_ticks: Option<String>,
+    pub export_every: Option<usize>,
+    pub overwrite: Option<Overwrite>,
+    pub sheet_columns: Option<usize>,
+    // width of one thumbnail in pixels
+    pub sheet_cell_width: Option<f64>,
+    // read the opened files again when they change
+    pub auto_reload: Option<bool>,
+    // action of the viewer and the code of its key, e.g. "next": "KeyD"
+    pub keys: Option<BTreeMap<String, String>>,
+}
+
+fn defaults() -> Settings {
+    let inkscape_path = if cfg!(windows) {
+        "C:/Program files/Inkscape/bin/inkscape.exe"
+    } else {
+        "inkscape"
+    };
+    Settings {
+        conversion_tool: Some("builtin".to_string()),
+        inkscape_path: Some(inkscape_path.to_string()),
+        conversion_timeout: Some(30),
+        conversion_attempts: Some(3),
+        frame_resolution: Some(1080),
+        max_threads: Some(4),
+        animation_resolution: Some(600),
+        gif_palette_speed: Some(10),
+        export_messages: Some(true),
+        export_legend: Some(false),
+        theme: Some("dark".to_string()),
+        transparent_background: Some(false),
+        output_dir: Some("frames".to_string()),
+        file_template: Some("{name}_{frame:05}".to_string()),
+        export_frames: Some(String::new()),
+        export_ticks: Some(String::new()),
+        export_every: Some(1),
+        overwrite: Some(Overwrite::Rename),
+        sheet_columns: Some(5),
+        sheet_cell_width: Some(320.0),
+        auto_reload: Some(true),
+        keys: Some(crate::keys::defaults()),
+        ..Settings::default()
+    }
+}
+
+// read once, until settings are saved or overridden
+static SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
+// `--set key=value` from the command line
+static OVERRIDES: Mutex<Vec<(String, Value)>> = Mutex::new(Vec::new());
+
+/// Built-in defaults, then `settings.json` next to the executable, the user config, `.rviewer.json`
+/// in the current folder, `RVIEWER_*` environment variables and `--set` options, each overrides the previous ones.
+/// Nothing is written, settings that can't be used are reported and skipped.
+pub fn get_settings() -> Settings {
+    SETTINGS.lock().unwrap().get_or_insert_with(load).clone()
+}
+
+fn load() -> Settings {
+    let defaults = to_map(&defaults());
+    let mut merged = defaults.clone();
+    let mut layers = Vec::new();
+    for path in config_paths() {
+        if let Some(layer) = read_file(&path) {
+            layers.push((path.display().to_string(), layer));
+        }
+    }
+    layers.push(("environment".to_string(), env_layer()));
+    layers.push(("--set".to_string(), OVERRIDES.lock().unwrap().clone()));
+
+    for (source, layer) in layers {
+        for (key, value) in layer {
+            let value = match check(&key, value) {
+                Ok(value) => value,
+                Err(e) => {
+                    eprintln!("Ignoring a setting from {}, {}", source, e);
+                    continue;
+                }
+            };
+            match (merged.get_mut(&key), value) {
+                // keys are set one by one
+                (Some(Value::Object(old)), Value::Object(new)) => old.extend(new),
+                // `null` undoes the previous layers
+                (_, Value::Null) => match defaults.get(&key) {
+                    Some(default) => {
+                        merged.insert(key, default.clone());
+                    }
+                    None => {
+                        merged.remove(&key);
+                    }
+                },
+                (_, value) => {
+                    merged.insert(key, value);
+                }
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(merged)).unwrap()
+}
+
+/// Settings given on the command line, as `key=value` with a json value or a plain string.
+pub fn set_overrides(options: &[String]) -> Result<(), String> {
+    let mut overrides = Vec::new();
+    for option in options {
+        let (key, value) = option.split_once('=').ok_or(format!("expected KEY=VALUE, got \"{}\"", option))?;
+        overrides.push((key.to_string(), parse_value(key, value)?));
+    }
+    *OVERRIDES.lock().unwrap() = overrides;
+    *SETTINGS.lock().unwrap() = None;
+    Ok(())
+}
+
+/// Writes the settings that are set to the user config, other settings in it are kept.
+pub fn save_settings(settings: &Settings) {
+    let path = match user_config_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("Can't save settings, no config folder");
+            return;
+        }
+    };
+    let mut saved = Map::new();
+    if path.is_file() {
+        // a file that can't be read is left as it is
+        match read_file(&path) {
+            Some(layer) => saved.extend(layer),
+            None => return,
+        }
+    }
+    saved.extend(to_map(settings));
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, serde_json::to_string_pretty(&saved).unwrap()));
+    if let Err(e) = result {
+        eprintln!("Can't save settings to {}, {}", path.display(), e);
+    }
+    *SETTINGS.lock().unwrap() = None;
+}
+
+/// Files settings are read from, in order.
+pub fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    // where older versions kept their settings
+    if let Ok(mut path) = env::current_exe() {
+        path.pop();
+        path.push("settings.json");
+        paths.push(path);
+    }
+    paths.extend(user_config_path());
+    paths.push(PathBuf::from(".rviewer.json"));
+    paths
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("rviewer").join("config.json"))
+}
+
+// `None` if there is no such file
+fn read_file(path: &Path) -> Option<Vec<(String, Value)>> {
+    let text = fs::read_to_string(path).ok()?;
+    match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(map)) => Some(map.into_iter().collect()),
+        Ok(_) => {
+            eprintln!("Can't read settings from {}, expected a json object", path.display());
+            None
+        }
+        Err(e) => {
+            eprintln!("Can't read settings from {}, {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// `RVIEWER_MAX_THREADS=8` sets `max_threads`
+fn env_layer() -> Vec<(String, Value)> {
+    let mut layer = Vec::new();
+    for (name, value) in env::vars() {
+        let key = match name.strip_prefix("RVIEWER_") {
+            Some(key) => key.to_lowercase(),
+            None => continue,
+        };
+        match parse_value(&key, &value) {
+            Ok(value) => layer.push((key, value)),
+            Err(e) => eprintln!("Ignoring {}, {}", name, e),
+        }
+    }
+    layer
+}
+
+// json if it fits the setting, otherwise a string, so that `theme=light` needs no quotes
+fn parse_value(key: &str, value: &str) -> Result<Value, String> {
+    match serde_json::from_str(value).map(|value| check(key, value)) {
+        Ok(Ok(value)) => Ok(value),
+        _ => check(key, Value::String(value.to_string())),
+    }
+}
+
+// a setting alone, so that errors name it
+fn check(key: &str, value: Value) -> Result<Value, String> {
+    let known = match serde_json::to_value(Settings::default()).unwrap() {
+        Value::Object(map) => map.contains_key(key),
+        _ => false,
+    };
+    if !known {
+        return Err(format!("unknown setting \"{}\"", key));
+    }
+    let mut map = Map::new();
+    map.insert(key.to_string(), value.clone());
+    match serde_json::from_value::<Settings>(Value::Object(map)) {
+        Ok(_) => Ok(value),
+        Err(e) => Err(format!("{}: {}", key, e)),
+    }
+}
+
+// only the settings that are set
+fn to_map(settings: &Settings) -> Map<String, Value> {
+    match serde_json::to_value(settings).unwrap() {
+        Value::Object(mut map) => {
+            map.retain(|_, value| !value.is_null());
+            map
+        }
+        _ => unreachable!(),
+    }
 }
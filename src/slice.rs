@@ -0,0 +1,165 @@
+use crate::app_data::DrawProperties;
+use crate::figure::{figure_name, Object};
+use crate::loader::{parse_line, Line, Setting};
+
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+/// Which ticks and objects are written to the new replay, everything if not set.
+/// Ranges start from 0, frames include in-betweens.
+#[derive(Default)]
+pub struct Selection {
+    pub frames: Vec<Range<usize>>,
+    pub ticks: Vec<Range<usize>>,
+    pub tags: Option<Vec<String>>,
+    pub figures: Option<Vec<String>>,
+}
+
+impl Selection {
+    fn has_object(&self, item: &Object) -> bool {
+        let tags = self.tags.as_ref();
+        let figures = self.figures.as_ref();
+        tags.is_none_or(|tags| item.tags().iter().any(|tag| tags.contains(tag)))
+            && figures.is_none_or(|figures| figures.iter().any(|name| name == figure_name(item)))
+    }
+
+    fn has_tick(&self, tick: usize, frames: Range<usize>) -> bool {
+        let frame_selected = self.frames.is_empty() || self.frames.iter().any(|range| range.start < frames.end && frames.start < range.end);
+        let tick_selected = self.ticks.is_empty() || self.ticks.iter().any(|range| range.contains(&tick));
+        frame_selected && tick_selected
+    }
+}
+
+/// Writes the selected part of a replay as a new replay. A tick is buffered until it ends,
+/// settings and kept objects from the ticks left out go to the next written tick, where they
+/// apply to the same frames as in the original.
+struct Slicer<W: Write> {
+    writer: W,
+    selection: Selection,
+    draw_properties: DrawProperties,
+    // None before the first tick
+    tick: Option<usize>,
+    lines: Vec<Line>,
+    kept: Vec<Object>,
+    settings: Vec<Setting>,
+    in_betweens: usize,
+    frames: usize,
+    written_ticks: usize,
+    unparsed: usize,
+}
+
+impl<W: Write> Slicer<W> {
+    fn push_line(&mut self, line: &str) -> io::Result<()> {
+        match parse_line(line, &mut self.draw_properties) {
+            Line::Tick => {
+                self.finish_tick()?;
+                self.tick = Some(self.tick.map_or(0, |tick| tick + 1));
+            }
+            Line::Setting(setting) => {
+                if let Setting::InBetweens(frames) = setting {
+                    self.in_betweens = frames;
+                }
+                self.lines.push(Line::Setting(setting));
+            }
+            Line::Object(x) if self.selection.has_object(&x) => self.lines.push(Line::Object(x)),
+            Line::Object(_) => {}
+            Line::Unparsed => self.unparsed += 1,
+        }
+        Ok(())
+    }
+
+    fn finish_tick(&mut self) -> io::Result<()> {
+        let lines = std::mem::take(&mut self.lines);
+        let selected = match self.tick {
+            Some(tick) => {
+                // the same number of frames as the loader makes
+                let frames = if tick == 0 { 1 } else { self.in_betweens };
+                let selected = self.selection.has_tick(tick, self.frames..self.frames + frames);
+                self.frames += frames;
+                selected
+            }
+            None => true,
+        };
+        if selected && self.tick.is_some() {
+            writeln!(self.writer, "tick")?;
+            for setting in self.settings.drain(..) {
+                writeln!(self.writer, "{}", setting.to_line())?;
+            }
+            for item in self.kept.drain(..) {
+                writeln!(self.writer, "{}", item.to_line())?;
+            }
+            self.written_ticks += 1;
+        }
+        for line in lines {
+            match line {
+                Line::Setting(setting) if selected => writeln!(self.writer, "{}", setting.to_line())?,
+                Line::Setting(setting) => self.settings.push(setting),
+                Line::Object(x) if selected => writeln!(self.writer, "{}", x.to_line())?,
+                Line::Object(x) if x.keep() => self.kept.push(x),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a replay and writes the selected ticks and objects, returns the number of written ticks.
+pub fn slice<R: BufRead, W: Write>(mut reader: R, writer: W, selection: Selection) -> io::Result<usize> {
+    let mut slicer = Slicer {
+        writer,
+        selection,
+        draw_properties: DrawProperties::new(),
+        tick: None,
+        lines: Vec::new(),
+        kept: Vec::new(),
+        settings: Vec::new(),
+        in_betweens: 1,
+        frames: 0,
+        written_ticks: 0,
+        unparsed: 0,
+    };
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        slicer.push_line(line.trim_end_matches(&['\n', '\r'][..]))?;
+        line.clear();
+    }
+    slicer.finish_tick()?;
+    slicer.writer.flush()?;
+    if slicer.unparsed > 0 {
+        eprintln!("unparsed: {}", slicer.unparsed);
+    }
+    Ok(slicer.written_ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+    use crate::replay::Frame;
+
+    use std::io::BufReader;
+
+    fn frames_of_ticks(replay: &[u8]) -> Vec<usize> {
+        let mut frames = Vec::<Frame>::new();
+        loader::read(BufReader::new(replay), |update| frames.extend(update.frames));
+        let ticks = frames.last().map_or(0, |frame| frame.tick + 1);
+        (0..ticks).map(|tick| frames.iter().filter(|frame| frame.tick == tick).count()).collect()
+    }
+
+    #[test]
+    fn settings_of_skipped_ticks_apply_to_the_same_frames() {
+        let replay = "size (10,10)\ntick\nrect c=(1,1) s=(1,1)\ntick\nrect c=(2,2) s=(1,1)\ntick\nin_betweens 4\nrect c=(3,3) s=(1,1)\ntick\nrect c=(4,4) s=(1,1)\ntick\n";
+        let selection = Selection {
+            ticks: vec![0..2, 3..5],
+            ..Selection::default()
+        };
+        let mut sliced = Vec::new();
+        assert_eq!(slice(replay.as_bytes(), &mut sliced, selection).unwrap(), 4);
+
+        let original = frames_of_ticks(replay.as_bytes());
+        let sliced = frames_of_ticks(&sliced);
+        assert_eq!(original, [1, 1, 4, 4, 4]);
+        // the skipped tick is the third one
+        assert_eq!(sliced, [original[0], original[1], original[3], original[4]]);
+    }
+}
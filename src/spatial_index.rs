@@ -0,0 +1,89 @@
+use druid::Rect;
+
+const NODE_CAPACITY: usize = 16;
+
+struct Node {
+    bounds: Rect,
+    first: usize,
+    len: usize,
+}
+
+/// Static R-tree over the bounds of objects in one frame, packed with sort-tile-recursive.
+///
+/// Items are identified by their position in the frame, so that query results can be sorted
+/// back into drawing order.
+pub struct SpatialIndex {
+    entries: Vec<(Rect, usize)>,
+    levels: Vec<Vec<Node>>,
+    unbounded: Vec<usize>,
+}
+
+impl SpatialIndex {
+    /// Items without bounds are returned by every query.
+    pub fn new(items: impl IntoIterator<Item = (Option<Rect>, usize)>) -> Self {
+        let mut entries = Vec::new();
+        let mut unbounded = Vec::new();
+        for (bounds, item) in items {
+            match bounds {
+                Some(bounds) => entries.push((bounds, item)),
+                None => unbounded.push(item),
+            }
+        }
+
+        let leaves = entries.len().div_ceil(NODE_CAPACITY);
+        let slice_len = ((leaves as f64).sqrt().ceil() as usize).max(1) * NODE_CAPACITY;
+        entries.sort_by(|a, b| a.0.center().x.total_cmp(&b.0.center().x));
+        for slice in entries.chunks_mut(slice_len) {
+            slice.sort_by(|a, b| a.0.center().y.total_cmp(&b.0.center().y));
+        }
+
+        let mut levels: Vec<Vec<Node>> = Vec::new();
+        let mut bounds = entries.iter().map(|(bounds, _)| *bounds).collect::<Vec<_>>();
+        while bounds.len() > NODE_CAPACITY || (levels.is_empty() && !bounds.is_empty()) {
+            let nodes = bounds
+                .chunks(NODE_CAPACITY)
+                .enumerate()
+                .map(|(i, chunk)| Node {
+                    bounds: chunk.iter().skip(1).fold(chunk[0], |acc, r| acc.union(*r)),
+                    first: i * NODE_CAPACITY,
+                    len: chunk.len(),
+                })
+                .collect::<Vec<_>>();
+            bounds = nodes.iter().map(|node| node.bounds).collect();
+            levels.push(nodes);
+        }
+
+        Self { entries, levels, unbounded }
+    }
+
+    /// Appends all items intersecting `rect` to `result`, in no particular order.
+    pub fn query(&self, rect: Rect, result: &mut Vec<usize>) {
+        result.extend_from_slice(&self.unbounded);
+        if let Some(top) = self.levels.last() {
+            for i in 0..top.len() {
+                self.visit(self.levels.len() - 1, i, rect, result);
+            }
+        }
+    }
+
+    fn visit(&self, level: usize, i: usize, rect: Rect, result: &mut Vec<usize>) {
+        let node = &self.levels[level][i];
+        if !intersects(node.bounds, rect) {
+            return;
+        }
+        for j in node.first..node.first + node.len {
+            if level == 0 {
+                if intersects(self.entries[j].0, rect) {
+                    result.push(self.entries[j].1);
+                }
+            } else {
+                self.visit(level - 1, j, rect, result);
+            }
+        }
+    }
+}
+
+// unlike Rect::intersect, touching and zero-sized rects (e.g. horizontal lines) count
+fn intersects(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
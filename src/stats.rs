@@ -0,0 +1,140 @@
+use crate::figure::figure_name;
+use crate::replay::Frames;
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use serde::Serialize;
+
+// how many of the largest frames are listed
+const LARGEST_FRAMES: usize = 10;
+
+/// Summary of a loaded replay, frames and ticks are numbered from 1.
+#[derive(Serialize)]
+pub struct Stats {
+    pub ticks: usize,
+    pub frames: usize,
+    pub objects: usize,
+    pub objects_per_frame: PerFrame,
+    // the number of objects in all frames, so objects shown on many frames count many times
+    pub figures: BTreeMap<&'static str, usize>,
+    pub tags: BTreeMap<String, usize>,
+    pub untagged: usize,
+    // in the last frame, where all of them are
+    pub kept: usize,
+    // reading and parsing, for indexed files frames are parsed while collecting
+    pub parse_seconds: f64,
+    pub largest_frames: Vec<FrameSize>,
+}
+
+#[derive(Serialize)]
+pub struct PerFrame {
+    pub min: usize,
+    pub avg: f64,
+    pub max: usize,
+}
+
+#[derive(Serialize)]
+pub struct FrameSize {
+    pub frame: usize,
+    pub tick: usize,
+    pub objects: usize,
+}
+
+/// Goes through all frames, for indexed files they are parsed here. `start` is when reading began.
+pub fn collect(frames: &Frames, start: Instant) -> Stats {
+    let mut figures = BTreeMap::new();
+    let mut tags = BTreeMap::<String, usize>::new();
+    let mut untagged = 0;
+    let mut sizes = Vec::with_capacity(frames.len());
+    for ind in 0..frames.len() {
+        let frame = frames.get(ind).unwrap();
+        for item in frame.objects.iter() {
+            *figures.entry(figure_name(item)).or_insert(0) += 1;
+            if item.tags().is_empty() {
+                untagged += 1;
+            }
+            for tag in item.tags() {
+                match tags.get_mut(tag) {
+                    Some(count) => *count += 1,
+                    None => {
+                        tags.insert(tag.clone(), 1);
+                    }
+                }
+            }
+        }
+        sizes.push(FrameSize {
+            frame: ind + 1,
+            tick: frame.tick + 1,
+            objects: frame.objects.len(),
+        });
+    }
+    let parse_time = start.elapsed();
+
+    let last = frames.len().checked_sub(1).and_then(|ind| frames.get(ind));
+    let objects = sizes.iter().map(|size| size.objects).sum();
+    let objects_per_frame = PerFrame {
+        min: sizes.iter().map(|size| size.objects).min().unwrap_or(0),
+        avg: objects as f64 / sizes.len().max(1) as f64,
+        max: sizes.iter().map(|size| size.objects).max().unwrap_or(0),
+    };
+    // the first of equal frames stays first
+    sizes.sort_by_key(|size| Reverse(size.objects));
+    sizes.truncate(LARGEST_FRAMES);
+    Stats {
+        ticks: last.as_ref().map_or(0, |frame| frame.tick + 1),
+        frames: frames.len(),
+        objects,
+        objects_per_frame,
+        figures,
+        tags,
+        untagged,
+        kept: last.map_or(0, |frame| frame.objects.iter().filter(|item| item.keep()).count()),
+        parse_seconds: parse_time.as_secs_f64(),
+        largest_frames: sizes,
+    }
+}
+
+impl Stats {
+    pub fn print(&self) {
+        println!("ticks: {}", self.ticks);
+        println!("frames: {}", self.frames);
+        println!("objects in all frames: {}", self.objects);
+        let per_frame = &self.objects_per_frame;
+        println!(
+            "objects per frame: min {}, avg {:.1}, max {}",
+            per_frame.min, per_frame.avg, per_frame.max
+        );
+        println!("kept objects: {}", self.kept);
+        println!("parse time: {:.2}s", self.parse_seconds);
+
+        println!("figures:");
+        print_counts(self.figures.iter().map(|(name, count)| (name.to_string(), *count)), self.objects);
+        println!("tags:");
+        let untagged = Some(("(no tags)".to_string(), self.untagged)).filter(|_| self.untagged > 0);
+        print_counts(self.tags.iter().map(|(tag, count)| (tag.clone(), *count)).chain(untagged), self.objects);
+
+        println!("largest frames:");
+        for size in self.largest_frames.iter() {
+            println!("  frame {} (tick {}): {} objects", size.frame, size.tick, size.objects);
+        }
+    }
+}
+
+// the biggest first, with their share of all objects
+fn print_counts(counts: impl Iterator<Item = (String, usize)>, total: usize) {
+    let mut counts = counts.collect::<Vec<_>>();
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+    let total = total.max(1);
+    let width = counts.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, count) in counts {
+        println!(
+            "  {:<width$}  {:>10}  {:>5.1}%",
+            name,
+            count,
+            count as f64 * 100.0 / total as f64,
+            width = width
+        );
+    }
+}
@@ -1,8 +1,12 @@
-use druid::{Point, Size};
+use druid::{Color, Point, Rect, Size};
 
 pub struct SvgParams<'a> {
     pub size: Size,
     pub width_scale: f64,
     pub flipy: bool,
     pub transform: &'a dyn Fn(Point) -> Point,
+    // part of the image that is exported, text that is not attached to input coordinates is placed in it
+    pub viewport: Rect,
+    pub overlay_font: f64,
+    pub overlay_color: Color,
 }
@@ -0,0 +1,79 @@
+use crate::settings::Settings;
+
+use druid::{theme, Color, Env, Key};
+
+pub const CANVAS_BACKGROUND: Key<Color> = Key::new("rviewer.canvas-background");
+pub const OVERLAY_TEXT_COLOR: Key<Color> = Key::new("rviewer.overlay-text-color");
+pub const DIFF_COLOR: Key<Color> = Key::new("rviewer.diff-color");
+
+/// Colors of the viewer and of exported images.
+#[derive(Clone)]
+pub struct Theme {
+    light: bool,
+    pub canvas: Color,
+    pub text: Color,
+    // outlines of objects that only one of compared panes has
+    pub diff: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            light: false,
+            canvas: Color::rgb8(41, 41, 41),
+            text: Color::rgb8(255, 255, 255),
+            diff: Color::rgb8(255, 160, 0),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            light: true,
+            canvas: Color::rgb8(255, 255, 255),
+            text: Color::rgb8(0, 0, 0),
+            diff: Color::rgb8(230, 90, 0),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            _ => {
+                eprintln!("Unknown theme {}, using dark", name);
+                Self::dark()
+            }
+        }
+    }
+
+    /// The theme named in the settings, with colors set there.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mut theme = Self::from_name(settings.theme.as_ref().unwrap());
+        let set = |color: Option<[u8; 3]>, target: &mut Color| {
+            if let Some([r, g, b]) = color {
+                *target = Color::rgb8(r, g, b);
+            }
+        };
+        set(settings.canvas_color, &mut theme.canvas);
+        set(settings.text_color, &mut theme.text);
+        set(settings.diff_color, &mut theme.diff);
+        theme
+    }
+
+    /// The dark theme keeps druid defaults for widgets.
+    pub fn configure_env(&self, env: &mut Env) {
+        env.set(CANVAS_BACKGROUND, self.canvas.clone());
+        env.set(OVERLAY_TEXT_COLOR, self.text.clone());
+        env.set(DIFF_COLOR, self.diff.clone());
+        if self.light {
+            env.set(theme::WINDOW_BACKGROUND_COLOR, Color::rgb8(0xf0, 0xf0, 0xf0));
+            env.set(theme::TEXT_COLOR, Color::rgb8(0x20, 0x20, 0x20));
+            env.set(theme::BACKGROUND_LIGHT, Color::rgb8(0xe8, 0xe8, 0xe8));
+            env.set(theme::BACKGROUND_DARK, Color::rgb8(0xd0, 0xd0, 0xd0));
+            env.set(theme::FOREGROUND_LIGHT, Color::rgb8(0xff, 0xff, 0xff));
+            env.set(theme::FOREGROUND_DARK, Color::rgb8(0xd8, 0xd8, 0xd8));
+            env.set(theme::BORDER_DARK, Color::rgb8(0xa0, 0xa0, 0xa0));
+            env.set(theme::BORDER_LIGHT, Color::rgb8(0x60, 0x60, 0x60));
+        }
+    }
+}
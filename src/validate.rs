@@ -0,0 +1,410 @@
+use crate::app_data::DrawProperties;
+use crate::figure::text::split_text;
+use crate::loader::{parse_line, Line, Setting};
+use crate::parse::{Param, Params};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead};
+use std::mem;
+
+use druid::{Color, Point};
+
+// messages of one kind that are printed, the rest are only counted
+const MAX_REPORTED: usize = 20;
+
+const SETTINGS: [&str; 11] = [
+    "speed",
+    "width",
+    "font",
+    "size",
+    "shift",
+    "svgwidth",
+    "background",
+    "flipy",
+    "disable",
+    "in_betweens",
+    "setfunc",
+];
+
+const FIGURES: [&str; 7] = ["rect", "circle", "line", "grid", "poly", "text", "msg"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Check {
+    Malformed,
+    UnknownFigure,
+    IgnoredParam,
+    UndefinedFunc,
+    FuncLength,
+    DuplicateId,
+    InBetweens,
+    UnusedTag,
+}
+
+impl Check {
+    fn is_error(self) -> bool {
+        !matches!(self, Check::IgnoredParam | Check::UnusedTag)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Check::Malformed => "malformed lines",
+            Check::UnknownFigure => "unknown figures",
+            Check::IgnoredParam => "ignored parameters",
+            Check::UndefinedFunc => "undefined functions",
+            Check::FuncLength => "functions of a wrong length",
+            Check::DuplicateId => "duplicate ids",
+            Check::InBetweens => "objects that can't be interpolated",
+            Check::UnusedTag => "unused disabled tags",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Pair,
+    Dims,
+    Number,
+    Int,
+    Flag,
+    Color,
+    Alignment,
+    Word,
+}
+
+// parameters every object except `msg` has
+const COMMON_PARAMS: [(&str, Kind); 5] = [
+    ("col", Kind::Color),
+    ("t", Kind::Word),
+    ("k", Kind::Flag),
+    ("id", Kind::Int),
+    ("fu", Kind::Word),
+];
+
+// own parameters of each figure, `None` for unknown figures
+fn params_of(figure: &str) -> Option<&'static [(&'static str, Kind)]> {
+    use Kind::*;
+    Some(match figure {
+        "rect" => &[("c", Pair), ("a", Alignment), ("s", Pair), ("f", Flag), ("w", Number)],
+        "circle" => &[("c", Pair), ("r", Number), ("arc", Pair), ("f", Flag), ("w", Number)],
+        "line" => &[("s", Pair), ("f", Pair), ("w", Number)],
+        "grid" => &[("c", Pair), ("a", Alignment), ("s", Pair), ("d", Dims), ("w", Number)],
+        "poly" => &[("p", Pair), ("f", Flag), ("w", Number)],
+        "text" => &[("c", Pair), ("a", Alignment), ("s", Number)],
+        "msg" => &[],
+        _ => return None,
+    })
+}
+
+// the same parsing as for drawing, so that a valid value is never replaced by a default
+fn is_valid(kind: Kind, value: &str) -> bool {
+    let value = &[value];
+    match kind {
+        Kind::Pair => <Point as Param>::from(value).is_some(),
+        Kind::Dims => <(usize, usize) as Param>::from(value).is_some(),
+        Kind::Number => <f64 as Param>::from(value).is_some(),
+        Kind::Int => <i32 as Param>::from(value).is_some(),
+        Kind::Flag => <bool as Param>::from(value).is_some(),
+        Kind::Color => <Color as Param>::from(value).is_some(),
+        Kind::Alignment => <(char, char) as Param>::from(value).is_some_and(|(x, y)| "BCE".contains(x) && "BCE".contains(y)),
+        Kind::Word => true,
+    }
+}
+
+// object with an id, as much as in-betweens need to know about it
+#[derive(Clone)]
+struct Item {
+    id: i32,
+    figure: String,
+    points: usize,
+    line: usize,
+}
+
+/// Checks a replay line by line, the same way the loader reads it, and prints what is wrong.
+pub struct Validator {
+    name: String,
+    line: usize,
+    draw_properties: DrawProperties,
+    in_betweens: usize,
+    funcs: HashSet<String>,
+    // name, number of values and the line of functions set in this tick
+    new_funcs: Vec<(String, usize, usize)>,
+    undefined_funcs: HashSet<String>,
+    disabled: Vec<(String, usize)>,
+    tags: HashSet<String>,
+    is_initial_tick: bool,
+    init_items: Vec<Item>,
+    items: Vec<Item>,
+    prev_items: Option<Vec<Item>>,
+    // kept objects are in many frames, but each pair of lines is reported once
+    reported: HashSet<(Check, usize, usize)>,
+    counts: HashMap<Check, usize>,
+}
+
+impl Validator {
+    /// `name` of the file is used in messages.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            line: 0,
+            draw_properties: DrawProperties::new(),
+            in_betweens: 1,
+            funcs: HashSet::new(),
+            new_funcs: Vec::new(),
+            undefined_funcs: HashSet::new(),
+            disabled: Vec::new(),
+            tags: HashSet::new(),
+            is_initial_tick: true,
+            init_items: Vec::new(),
+            items: Vec::new(),
+            prev_items: None,
+            reported: HashSet::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn push_line(&mut self, text: &str) {
+        self.line += 1;
+        if text.trim().is_empty() {
+            return;
+        }
+        // the loader only looks at the start of the line, so `rectangle` is a rect and `size=(10,20)` is a size
+        let name = ["tick"]
+            .iter()
+            .chain(SETTINGS.iter())
+            .chain(FIGURES.iter())
+            .find(|name| text.starts_with(*name));
+        match name.copied() {
+            Some("tick") => self.tick(),
+            Some(name) if SETTINGS.contains(&name) => self.setting(text, name),
+            Some(name) => self.object(text, name, params_of(name).unwrap()),
+            None => {
+                let word = text.split([' ', '=']).next().unwrap();
+                if word.is_empty() {
+                    self.report(Check::Malformed, "expected a figure or a setting at the start of the line".to_string());
+                } else {
+                    self.report(Check::UnknownFigure, format!("unknown figure \"{}\"", word));
+                }
+            }
+        }
+    }
+
+    /// Prints the summary, `false` if there are errors.
+    pub fn finish(mut self) -> bool {
+        self.end_frame();
+        self.check_funcs();
+
+        for (tag, line) in mem::take(&mut self.disabled) {
+            if !self.tags.contains(&tag) {
+                self.report_at(line, Check::UnusedTag, format!("tag {} is disabled, but no object has it", tag));
+            }
+        }
+
+        let mut errors = 0;
+        let mut warnings = 0;
+        let mut counts = self.counts.into_iter().collect::<Vec<_>>();
+        counts.sort();
+        for (check, count) in counts {
+            if count > MAX_REPORTED {
+                println!("{}: {} more {}", self.name, count - MAX_REPORTED, check.label());
+            }
+            if check.is_error() {
+                errors += count;
+            } else {
+                warnings += count;
+            }
+        }
+        println!("{}: {} errors, {} warnings", self.name, errors, warnings);
+        errors == 0
+    }
+
+    fn report(&mut self, check: Check, message: String) {
+        self.report_at(self.line, check, message);
+    }
+
+    fn report_at(&mut self, line: usize, check: Check, message: String) {
+        let count = self.counts.entry(check).or_insert(0);
+        *count += 1;
+        if *count <= MAX_REPORTED {
+            let severity = if check.is_error() { "error" } else { "warning" };
+            println!("{}:{}: {}: {}", self.name, line, severity, message);
+        }
+    }
+
+    fn setting(&mut self, text: &str, name: &str) {
+        match parse_line(text, &mut self.draw_properties) {
+            Line::Setting(Setting::InBetweens(frames)) => {
+                self.in_betweens = frames;
+                self.funcs.insert("line".to_string());
+            }
+            Line::Setting(Setting::SetFunc(func, values)) => {
+                self.funcs.insert(func.clone());
+                self.new_funcs.push((func, values.len(), self.line));
+            }
+            Line::Setting(Setting::Disable(tag)) => self.disabled.push((tag, self.line)),
+            Line::Setting(_) => {}
+            _ => self.report(Check::Malformed, format!("can't parse {}", name)),
+        }
+    }
+
+    fn object(&mut self, text: &str, name: &str, params: &[(&str, Kind)]) {
+        // the text itself may contain anything
+        let rest = match name {
+            "text" => match split_text(text) {
+                Some((_, rest)) => rest,
+                None => {
+                    self.report(Check::Malformed, "text has no closing quote".to_string());
+                    return;
+                }
+            },
+            _ => text.to_string(),
+        };
+        if name != "msg" {
+            self.check_params(name, &rest, params);
+        }
+
+        let object = match parse_line(text, &mut self.draw_properties) {
+            Line::Object(object) => object,
+            _ => return,
+        };
+        self.tags.extend(object.tags().iter().cloned());
+        if let Some(func) = &object.common().func {
+            if !self.funcs.contains(func) && self.undefined_funcs.insert(func.clone()) {
+                self.report(Check::UndefinedFunc, format!("fu={} is not defined by setfunc before", func));
+            }
+        }
+        if let Some(id) = object.common().id {
+            let points = match name {
+                "poly" => Params::from_str(&rest).get::<Vec<Point>>("p").map_or(0, |points| points.len()),
+                _ => 0,
+            };
+            let item = Item {
+                id,
+                figure: name.to_string(),
+                points,
+                line: self.line,
+            };
+            if object.keep() {
+                self.init_items.push(item.clone());
+            }
+            self.items.push(item);
+        }
+    }
+
+    fn check_params(&mut self, name: &str, rest: &str, params: &[(&str, Kind)]) {
+        let mut seen = Vec::new();
+        for token in rest.split_whitespace().skip(1) {
+            let (key, value) = match token.split_once('=') {
+                Some((key, value)) if !value.is_empty() => (key, value),
+                _ if token == "k" => {
+                    self.report(Check::IgnoredParam, "k without a value is ignored, use k=1".to_string());
+                    continue;
+                }
+                _ => {
+                    self.report(Check::IgnoredParam, format!("\"{}\" has no value and is ignored", token));
+                    continue;
+                }
+            };
+            match params.iter().chain(COMMON_PARAMS.iter()).find(|(param, _)| *param == key) {
+                Some(&(_, kind)) if !is_valid(kind, value) => {
+                    self.report(Check::Malformed, format!("bad value of {}: \"{}\"", key, value));
+                }
+                // the only ones that can be repeated
+                Some(_) if key == "p" || key == "t" => {}
+                Some(_) if seen.contains(&key) => {
+                    self.report(Check::IgnoredParam, format!("{} is given twice, only the first one is used", key));
+                }
+                Some(_) => seen.push(key),
+                None => self.report(Check::IgnoredParam, format!("unknown parameter \"{}\" of {}", key, name)),
+            }
+        }
+    }
+
+    // functions are used with the in-betweens of the tick they are set in
+    fn check_funcs(&mut self) {
+        let in_betweens = self.in_betweens;
+        for (name, len, line) in mem::take(&mut self.new_funcs) {
+            if in_betweens > 1 && len != in_betweens - 1 {
+                let message = format!(
+                    "setfunc {} has {} values, in_betweens {} needs {}",
+                    name,
+                    len,
+                    in_betweens,
+                    in_betweens - 1
+                );
+                self.report_at(line, Check::FuncLength, message);
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        self.check_funcs();
+        if self.is_initial_tick {
+            self.init_items = mem::take(&mut self.items);
+        } else {
+            self.end_frame();
+        }
+        self.items = self.init_items.clone();
+        self.is_initial_tick = false;
+    }
+
+    fn end_frame(&mut self) {
+        let frame = mem::take(&mut self.items);
+
+        let mut ids = HashMap::new();
+        for item in frame.iter() {
+            match ids.get(&item.id) {
+                Some(&line) => {
+                    if self.reported.insert((Check::DuplicateId, line, item.line)) {
+                        let message = format!("id {} is already used in this frame at line {}", item.id, line);
+                        self.report_at(item.line, Check::DuplicateId, message);
+                    }
+                }
+                None => {
+                    ids.insert(item.id, item.line);
+                }
+            }
+        }
+
+        // the same objects as the loader interpolates: the last one with the id in the previous frame
+        if let Some(prev_items) = self.prev_items.as_ref().filter(|_| self.in_betweens > 1) {
+            let prev = prev_items.iter().map(|item| (item.id, item)).collect::<HashMap<_, _>>();
+            let mut problems = Vec::new();
+            for item in frame.iter() {
+                let a = match prev.get(&item.id) {
+                    Some(a) => a,
+                    None => continue,
+                };
+                if a.figure != item.figure {
+                    let message = format!("id {} is a {} at line {}, it can't become a {}", item.id, a.figure, a.line, item.figure);
+                    problems.push((a.line, item.line, message));
+                } else if a.points != item.points {
+                    let message = format!(
+                        "poly with id {} has {} points at line {}, but {} here",
+                        item.id, a.points, a.line, item.points
+                    );
+                    problems.push((a.line, item.line, message));
+                }
+            }
+            for (prev_line, line, message) in problems {
+                if self.reported.insert((Check::InBetweens, prev_line, line)) {
+                    self.report_at(line, Check::InBetweens, message);
+                }
+            }
+        }
+        self.prev_items = Some(frame);
+    }
+}
+
+/// Prints problems of the replay, `false` if there are errors.
+pub fn validate<R: BufRead>(mut reader: R, name: &str) -> io::Result<bool> {
+    let mut validator = Validator::new(name);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        validator.push_line(line.trim_end_matches(&['\n', '\r'][..]));
+    }
+    Ok(validator.finish())
+}
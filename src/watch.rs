@@ -0,0 +1,31 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// how often opened files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Size and modification time of a file, `None` if it can't be read.
+pub type FileState = Option<(u64, Option<SystemTime>)>;
+
+pub fn file_states(paths: &[String]) -> Vec<FileState> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).ok().map(|metadata| (metadata.len(), metadata.modified().ok())))
+        .collect()
+}
+
+/// Waits until some of the files change and stay the same for a while, so that they are not read
+/// while still being written. Returns the new states.
+pub fn wait_for_change(paths: &[String], seen: &[FileState]) -> Vec<FileState> {
+    let mut last = seen.to_vec();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let states = file_states(paths);
+        // a file is often removed and created again
+        if states != seen && states == last && states.iter().all(Option::is_some) {
+            return states;
+        }
+        last = states;
+    }
+}